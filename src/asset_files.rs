@@ -0,0 +1,16 @@
+//! Files the tools built into the game write back to the assets folder.
+
+use std::path::{Path, PathBuf};
+
+/// Where the asset server finds `path`, relative to `asset_folder`,
+/// so the file written there is the one the game loads whatever directory it runs from.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn resolve(asset_folder: &str, path: &Path) -> PathBuf {
+    bevy::asset::FileAssetIo::get_root_path().join(asset_folder).join(path)
+}
+
+/// Assets are served over HTTP, files can only be printed.
+#[cfg(target_arch = "wasm32")]
+pub fn resolve(asset_folder: &str, path: &Path) -> PathBuf {
+    Path::new(asset_folder).join(path)
+}
@@ -6,6 +6,51 @@ use crate::sprite::*;
 #[derive(Component, Default)]
 pub struct Background; //TODO: layers with paralax?
 
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BackgroundType {
+    #[default]
+    Blue,
+    Brown,
+    White,
+    Green,
+}
+
+impl std::str::FromStr for BackgroundType {
+
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<BackgroundType, Self::Err> {
+        match input {
+            "Blue" => Ok(BackgroundType::Blue),
+            "Brown" => Ok(BackgroundType::Brown),
+            "White" => Ok(BackgroundType::White),
+            "Green" => Ok(BackgroundType::Green),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for BackgroundType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+impl From<BackgroundType> for SpriteType {
+    fn from(background_type: BackgroundType) -> Self {
+        match background_type {
+            BackgroundType::Blue => SpriteType::BlueBG,
+            BackgroundType::Brown => SpriteType::BrownBG,
+            BackgroundType::White => SpriteType::WhiteBG,
+            BackgroundType::Green => SpriteType::GreenBG,
+        }
+    }
+}
+
+/// The background used by newly spawned background tiles.
+#[derive(Default)]
+pub struct CurrentBackground(pub BackgroundType);
+
 const IMAGE_SIZE: f32 = 24.0;
 const SCALE: f32 = 10.0;
 const TILE_SIZE: f32 = IMAGE_SIZE * SCALE;
@@ -33,14 +78,15 @@ fn in_horizontal_strip(tile_start: &Vec3, position: &Vec3) -> bool {
     0.0 <= difference && difference < TILE_SIZE
 }
 
-fn get_image(position: &Vec3) -> &'static str {
+fn get_image(position: &Vec3, background_type: BackgroundType) -> &'static str {
     let image = if position.y < CLOUD_HEIGHT - TILE_SIZE { SpriteTypeStates::Full } else if position.y >= CLOUD_HEIGHT { SpriteTypeStates::Empty } else { SpriteTypeStates::Half };
-    SPRITES[&SpriteType::BlueBG][&image]
+    SPRITES[&SpriteType::from(background_type)][&image]
 }
 
 pub fn spawn_background(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    background: Res<CurrentBackground>,
     windows: Res<Windows>,
     query: Query<(Entity, &Background, &Transform)>,
 ) {
@@ -50,6 +96,7 @@ pub fn spawn_background(
     spawn_tiles(
         &start_position,
         &window_size,
+        background.0,
         &mut commands,
         &asset_server,
         &query
@@ -59,6 +106,7 @@ pub fn spawn_background(
 pub fn update_background(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
+    background: Res<CurrentBackground>,
     windows: Res<Windows>,
     background_query: Query<(Entity, &Background, &Transform)>,
     camera_query: Query<(&MainCamera, &Transform)>,
@@ -70,6 +118,7 @@ pub fn update_background(
     spawn_tiles(
         &start_position,
         &window_size,
+        background.0,
         &mut commands,
         &asset_server,
         &background_query,
@@ -85,6 +134,7 @@ pub fn update_background(
 fn spawn_tiles(
     start_position: &Vec3,
     window_size: &Vec3,
+    background_type: BackgroundType,
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
     query: &Query<(Entity, &Background, &Transform)>,
@@ -100,7 +150,7 @@ fn spawn_tiles(
                 .any(|tile_position| in_tile(&tile_position, &position)) {
                 continue;
             }
-            let image = get_image(&position);
+            let image = get_image(&position, background_type);
             commands
                 .spawn_bundle(SpriteBundle {
                     texture: asset_server.get_handle(image),
@@ -131,6 +181,10 @@ fn despawn_tiles(
     }
 }
 
+pub fn reset_background(mut background: ResMut<CurrentBackground>) {
+    *background = CurrentBackground::default();
+}
+
 pub fn clear_background(
     mut commands: Commands,
     query: Query<(Entity, &Background)>,
@@ -0,0 +1,106 @@
+//! Checks every level listed in `assets/maps/chaz.levels.ron`, including Tiled maps, without launching the game.
+//! Map files the manifest does not list are not played, so they are only warned about.
+//!
+//! Usage: `chaz-levels [assets folder]`
+
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::ExitCode;
+
+use enum_iterator::IntoEnumIterator;
+
+use chaz::options::Difficulty;
+use chaz::state::game::levels::{read_level_manifest, LevelInfo, MANIFEST_PATH};
+use chaz::state::game::lint::{lint, LintError};
+use chaz::state::game::physics::{read_physics_config, PhysicsConfig, PHYSICS_PATH};
+
+/// Lints the level on every difficulty, since health and recorded runs depend on it.
+/// An error found on some difficulties only names them.
+fn check(assets: &Path, level: &LevelInfo, physics_config: &PhysicsConfig) -> Result<(), Vec<String>> {
+    let map = level.read_map_from(assets)?;
+    let file = assets.join(&level.map).display().to_string();
+    let difficulties = Difficulty::into_enum_iter().collect::<Vec<_>>();
+    let mut errors: Vec<(LintError, Vec<Difficulty>)> = Vec::new();
+    for &difficulty in &difficulties {
+        for error in lint(&map, physics_config, &level.id, difficulty) {
+            match errors.iter_mut().find(|(found, _)| *found == error) {
+                Some((_, failed_on)) => failed_on.push(difficulty),
+                None => errors.push((error, vec![difficulty])),
+            }
+        }
+    }
+    if errors.is_empty() {
+        return Ok(());
+    }
+    Err(errors.iter().map(|(error, failed_on)| if *failed_on == difficulties {
+        format!("{}: {}", file, error)
+    } else {
+        let failed_on = failed_on.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        format!("{}: {} on {}", file, error, failed_on)
+    }).collect())
+}
+
+const MAP_EXTENSIONS: [&str; 3] = ["chazmap", "tmj", "tmx"];
+
+fn main() -> ExitCode {
+    let assets = std::env::args().nth(1).unwrap_or_else(|| "assets".to_string());
+    let assets = Path::new(&assets);
+    let manifest_path = assets.join(MANIFEST_PATH);
+    let manifest = match std::fs::read_to_string(&manifest_path) {
+        Ok(text) => match read_level_manifest(&text) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                eprintln!("Could not read {}: {}", manifest_path.display(), e);
+                return ExitCode::FAILURE;
+            },
+        },
+        Err(e) => {
+            eprintln!("Could not read {}: {}", manifest_path.display(), e);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let listed = HashSet::<_>::from_iter(manifest.levels.iter().map(|level| assets.join(&level.map)));
+    let mut unlisted = match std::fs::read_dir(assets.join("maps")) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map_or(false, |extension| MAP_EXTENSIONS.iter().any(|e| extension == *e)))
+            .filter(|path| !listed.contains(path))
+            .collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+    unlisted.sort();
+    for map_path in unlisted {
+        println!("warn  {} is not listed in {}", map_path.display(), manifest_path.display());
+    }
+
+    let physics_path = assets.join(PHYSICS_PATH);
+    // the game would fall back to the default physics, which the levels are not made for
+    let physics_config = match std::fs::read_to_string(&physics_path).map(|text| read_physics_config(&text)) {
+        Ok(Ok(config)) => config,
+        Ok(Err(e)) => {
+            eprintln!("Could not read {}: {}", physics_path.display(), e);
+            return ExitCode::FAILURE;
+        },
+        Err(e) => {
+            eprintln!("Could not read {}: {}", physics_path.display(), e);
+            return ExitCode::FAILURE;
+        },
+    };
+
+    let mut failed = false;
+    for level in manifest.levels {
+        let map_path = assets.join(&level.map);
+        match check(assets, &level, &physics_config) {
+            Ok(()) => println!("ok    {}", map_path.display()),
+            Err(errors) => {
+                failed = true;
+                println!("FAIL  {}", map_path.display());
+                for error in errors {
+                    println!("    {}", error);
+                }
+            },
+        }
+    }
+    if failed { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
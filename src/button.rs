@@ -6,7 +6,7 @@ use crate::options::*;
 #[derive(Component)]
 pub enum Action {
     ChangeState(AppState),
-    Play { level: usize },
+    Play { level: String },
 }
 
 pub struct ButtonBuilder<S: Into<String>> {
@@ -61,7 +61,7 @@ pub fn buttons(
                 let screen = match action {
                     Action::ChangeState(screen) => *screen,
                     Action::Play { level } => {
-                        options.level = *level;
+                        options.level = level.clone();
                         AppState::Game
                     }
                 };
@@ -0,0 +1,29 @@
+#![feature(derive_default_enum)]
+
+use bevy::prelude::*;
+
+pub mod asset_files;
+pub mod sound;
+pub use sound::Sound;
+pub mod score;
+pub mod background;
+pub mod button;
+pub mod camera;
+pub mod controls;
+pub mod log;
+use log::*;
+pub mod screen;
+pub mod sprite;
+pub use sprite::SpriteHandles;
+pub mod state;
+pub mod options;
+pub mod storage;
+
+pub use state::*;
+
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate serde_derive;
+
+extern crate reqwest;
@@ -1,3 +1,4 @@
+#[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
 #[cfg(target_arch = "wasm32")]
@@ -8,8 +9,8 @@ extern "C" {
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-fn log(s: &str) {
-    println!(s);
+pub fn log(s: &str) {
+    println!("{}", s);
 }
 
 macro_rules! console_log {
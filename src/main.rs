@@ -1,39 +1,26 @@
-#![feature(derive_default_enum)]
-
+use bevy::asset::AssetServerSettings;
 use bevy::prelude::*;
 
-mod sound;
-use sound::Sound;
-mod score;
-mod background;
-mod button;
-mod camera;
-mod controls;
-mod log;
-use log::*;
-mod screen;
-mod sprite;
-use sprite::SpriteHandles;
-mod state;
-mod options;
-
-use state::*;
-
-#[macro_use]
-extern crate lazy_static;
-#[macro_use]
-extern crate serde_derive;
-
-extern crate reqwest;
+use chaz::*;
+use chaz::state::game::recorder::Recorder;
 
 fn main() {
     // When building for WASM, print panics to the browser console
     #[cfg(target_arch = "wasm32")]
         console_error_panic_hook::set_once();
 
-    console_log!("Starting Game!");
+    chaz::log::log("Starting Game!");
+    let asset_server_settings = AssetServerSettings {
+        // hot-reload maps and other assets while the game is running
+        watch_for_changes: cfg!(not(target_arch = "wasm32")),
+        ..Default::default()
+    };
+    let recorder = Recorder::from_args(std::env::args(), &asset_server_settings.asset_folder);
     App::new()
+        .insert_resource(asset_server_settings)
+        .insert_resource(recorder)
         .init_resource::<SpriteHandles>()
+        .init_resource::<background::CurrentBackground>()
         .add_event::<GameOverEvent>()
         .add_plugins(DefaultPlugins)
         .add_plugin(Sound)
@@ -42,8 +29,6 @@ fn main() {
         .add_plugin(Game)
         .add_plugin(GameOver)
         .add_plugin(Pause)
+        .add_plugin(Editor)
         .run();
 }
-
-
-
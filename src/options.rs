@@ -5,14 +5,14 @@ use std::fmt::*;
 #[derive(Clone, Debug)]
 pub struct LeaderBoardOptions {
     pub difficulty: Difficulty,
-    pub level: usize,
+    pub level: String,
 }
 
 impl Default for LeaderBoardOptions {
     fn default() -> Self {
         LeaderBoardOptions {
             difficulty: Difficulty::default(),
-            level: 1,
+            level: "1".to_string(),
         }
     }
 }
@@ -21,7 +21,7 @@ impl Default for LeaderBoardOptions {
 pub struct Options {
     pub name: String,
     pub difficulty: Difficulty,
-    pub level: usize,
+    pub level: String,
     pub music_volume: u32,
 }
 
@@ -30,7 +30,7 @@ impl Default for Options {
         Options {
             name: "Chaz".to_string(),
             difficulty: Difficulty::default(),
-            level: 1,
+            level: "1".to_string(),
             music_volume: 100,
         }
     }
@@ -50,7 +50,7 @@ pub trait Next {
     fn next(self) -> Self; //TODO: implement with a macro
 }
 
-#[derive(Clone, Component, Copy, Debug, Default, Deserialize, IntoEnumIterator, PartialEq)]
+#[derive(Clone, Component, Copy, Debug, Default, Deserialize, Eq, Hash, IntoEnumIterator, PartialEq)]
 #[serde(rename_all = "UPPERCASE")] //backend stores it like this
 pub enum Difficulty {
     Training,
@@ -67,6 +67,15 @@ impl std::fmt::Display for Difficulty {
     }
 }
 
+impl std::str::FromStr for Difficulty {
+
+    type Err = ();
+
+    fn from_str(input: &str) -> std::result::Result<Difficulty, Self::Err> {
+        Difficulty::into_enum_iter().find(|difficulty| difficulty.to_string() == input).ok_or(())
+    }
+}
+
 impl Option for Difficulty {
     fn add_to(self, options: &mut Options) {
         options.difficulty = self;
@@ -4,6 +4,8 @@ use enum_iterator::IntoEnumIterator;
 
 use crate::AppState;
 use crate::options::Options;
+use crate::state::game::levels::Levels;
+use crate::state::game::map::Map;
 
 pub struct Sound;
 
@@ -31,6 +33,7 @@ impl From<AppState> for Song {
         match state {
             AppState::PreLoad | AppState::Loading => Song::MainTheme,
             AppState::Menu | AppState::Options | AppState::LevelSelect | AppState::Leaderboard => Song::MainTheme,
+            AppState::Editor => Song::MainTheme,
             AppState::Game | AppState::Pause => Song::GameTheme,
             AppState::GameOver => Song::GameOverTheme,
         }
@@ -48,23 +51,40 @@ impl ToString for Song {
 }
 
 struct MusicId {
-    song: Song,
+    path: String,
     id: InstanceHandle,
 }
 
 #[derive(Default)]
 struct Music(Option<MusicId>);
 
-fn play_song(song: Song) -> impl Fn(Res<AssetServer>, Res<Audio>, ResMut<Music>) {
-    move |asset_server: Res<AssetServer>, audio: Res<Audio>, mut music: ResMut<Music>| {
+/// The map being played can replace the game theme with its own music.
+fn song_path(song: Song, options: &Options, levels: &Levels, maps: &Assets<Map>) -> String {
+    if song == Song::GameTheme {
+        let map = levels.map(&options.level).and_then(|handle| maps.get(handle));
+        if let Some(music) = map.and_then(|map| map.header.music.as_ref()) {
+            return music.clone();
+        }
+    }
+    song.to_string()
+}
+
+fn play_song(song: Song) -> impl Fn(Res<AssetServer>, Res<Audio>, Res<Options>, Res<Levels>, Res<Assets<Map>>, ResMut<Music>) {
+    move |asset_server: Res<AssetServer>,
+          audio: Res<Audio>,
+          options: Res<Options>,
+          levels: Res<Levels>,
+          maps: Res<Assets<Map>>,
+          mut music: ResMut<Music>| {
+        let path = song_path(song, &options, &levels, &maps);
         if let Some(music_id) = &music.0 {
-            if music_id.song == song {
+            if music_id.path == path {
                 return;
             }
             audio.stop();
         }
-        let id = audio.play_looped(asset_server.load(song.to_string().as_str()));
-        music.0 = Some(MusicId { song, id });
+        let id = audio.play_looped(asset_server.load(path.as_str()));
+        music.0 = Some(MusicId { path, id });
     }
 }
 
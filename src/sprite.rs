@@ -60,6 +60,9 @@ pub enum SpriteType {
     Ground,
     Heart,
     Torchlight,
+    Flag,
+    Coin,
+    Gem,
 }
 
 impl fmt::Display for SpriteType {
@@ -218,6 +221,17 @@ pub static ref SPRITES: HashMap<SpriteType, HashMap<SpriteTypeStates, &'static s
     (SpriteType::Torchlight, HashMap::from([
         (SpriteTypeStates::Full, "torch-light-effect.png"),
     ])),
+    (SpriteType::Flag, HashMap::from([
+        (SpriteTypeStates::Idle0, "pixel-platformer/Tiles/tile_0111.png"),
+        (SpriteTypeStates::Idle1, "pixel-platformer/Tiles/tile_0112.png"),
+    ])),
+    (SpriteType::Coin, HashMap::from([
+        (SpriteTypeStates::Idle0, "pixel-platformer/Tiles/tile_0151.png"),
+        (SpriteTypeStates::Idle1, "pixel-platformer/Tiles/tile_0152.png"),
+    ])),
+    (SpriteType::Gem, HashMap::from([
+        (SpriteTypeStates::Full, "pixel-platformer/Tiles/tile_0067.png"),
+    ])),
 ]);
 }
 
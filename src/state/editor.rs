@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use bevy::asset::AssetServerSettings;
+use bevy::prelude::*;
+use bevy_egui::*;
+
+use crate::background::*;
+use crate::camera::*;
+use crate::options::{Difficulty, Options};
+use crate::sprite::*;
+use crate::state::AppState;
+use crate::state::game::levels::{LevelInfo, Levels};
+use crate::state::game::lint::lint;
+use crate::state::game::map::{read_map, Map, Tile};
+use crate::state::game::physics::PhysicsConfig;
+use crate::state::game::spawn_tile_sprite;
+
+/// Edits the map of `Options.level` in place, so play testing and switching levels keep the changes.
+/// They only outlive the game once saved.
+pub struct Editor;
+
+impl Plugin for Editor {
+    fn build(&self, app: &mut App) {
+        app
+            .init_resource::<EditorSession>()
+            .add_system_set(SystemSet::on_enter(AppState::Editor).with_system(reset_camera_position))
+            .add_system_set(SystemSet::on_enter(AppState::Editor).with_system(spawn_background.after(spawn_map)))
+            .add_system_set(SystemSet::on_enter(AppState::Editor).with_system(spawn_map))
+            .add_system_set(SystemSet::on_update(AppState::Editor).with_system(show_editor_ui))
+            .add_system_set(SystemSet::on_update(AppState::Editor).with_system(paint))
+            .add_system_set(SystemSet::on_update(AppState::Editor).with_system(move_camera))
+            .add_system_set(SystemSet::on_update(AppState::Editor).with_system(update_background))
+            .add_system_set(SystemSet::on_exit(AppState::Editor).with_system(cleanup))
+            .add_system_set(SystemSet::on_exit(AppState::Editor).with_system(clear_background))
+            .add_system_set(SystemSet::on_exit(AppState::Editor).with_system(reset_background))
+            .add_system_set(SystemSet::on_enter(AppState::Menu).with_system(end_playtest));
+    }
+}
+
+pub struct EditorSession {
+    /// Set while playing a map from the editor, to offer going back to it.
+    pub playtesting: bool,
+    brush: Tile,
+    tiles: HashMap<(usize, usize), Entity>,
+    /// The outcome of the last save.
+    status: String,
+}
+
+impl Default for EditorSession {
+    fn default() -> Self {
+        EditorSession {
+            playtesting: false,
+            brush: Tile::Ground,
+            tiles: HashMap::new(),
+            status: String::new(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct EditorTile;
+
+fn respawn_tile(
+    i: usize,
+    j: usize,
+    map: &Map,
+    tiles: &mut HashMap<(usize, usize), Entity>,
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    spawn: impl FnMut(String) -> Handle<TextureAtlas>,
+) {
+    if let Some(entity) = tiles.remove(&(i, j)) {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some(tile_info) = map.get_tile_info(i, j) {
+        let entity = spawn_tile_sprite(commands, &tile_info, asset_server, spawn)
+            .insert(EditorTile)
+            .id();
+        tiles.insert((i, j), entity);
+    }
+}
+
+fn spawn_map(
+    maps: Res<Assets<Map>>,
+    levels: Res<Levels>,
+    options: Res<Options>,
+    mut state: ResMut<State<AppState>>,
+    mut session: ResMut<EditorSession>,
+    mut background: ResMut<CurrentBackground>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    sprite_handles: Res<SpriteHandles>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut textures: ResMut<Assets<Image>>,
+) {
+    let mut spawn = |name| {
+        spawn(
+            name,
+            &sprite_handles,
+            &mut texture_atlases,
+            &mut textures,
+        )
+    };
+    session.playtesting = false;
+    session.tiles.clear();
+    let map = match levels.map(&options.level).and_then(|handle| maps.get(handle)) {
+        Some(map) => map,
+        None => {
+            crate::console_log!("Level {} can not be edited, its map is missing", options.level);
+            state.set(AppState::Menu).unwrap();
+            return;
+        },
+    };
+    background.0 = map.header.background;
+    for (i, j) in map.iter() {
+        respawn_tile(i, j, map, &mut session.tiles, &mut commands, &asset_server, &mut spawn);
+    }
+}
+
+fn show_editor_ui(
+    mut egui_context: ResMut<EguiContext>,
+    mut options: ResMut<Options>,
+    mut session: ResMut<EditorSession>,
+    mut state: ResMut<State<AppState>>,
+    levels: Res<Levels>,
+    maps: Res<Assets<Map>>,
+    physics_config: Res<PhysicsConfig>,
+    asset_server_settings: Res<AssetServerSettings>,
+) {
+    use egui::*;
+    use enum_iterator::IntoEnumIterator;
+
+    let mut level = options.level.clone();
+    Window::new("Editor")
+        .collapsible(false)
+        .resizable(false)
+        .fixed_pos((10.0, 10.0))
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label("Level: ");
+            let selected_level = levels.get(&level)
+                .map(|level| level.name.clone())
+                .unwrap_or_else(|| level.clone());
+            ComboBox::from_id_source("Level select")
+                .selected_text(selected_level)
+                .show_ui(ui, |ui| {
+                    for level_info in levels.iter() {
+                        ui.selectable_value(&mut level, level_info.id.clone(), level_info.name.as_str());
+                    }
+                });
+            ui.label("Tile: ");
+            ui.horizontal_wrapped(|ui| {
+                for tile in Tile::into_enum_iter() {
+                    ui.selectable_value(&mut session.brush, tile, tile.to_string());
+                }
+            });
+            ui.label("Left click paints, right click erases, arrow keys move around.");
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    let map = levels.map(&options.level).and_then(|handle| maps.get(handle));
+                    session.status = match (levels.get(&options.level), map) {
+                        (Some(level), Some(map)) => {
+                            save(&asset_server_settings.asset_folder, level, map, &physics_config, options.difficulty)
+                                .unwrap_or_else(|error| error)
+                        },
+                        _ => "Nothing to save".to_string(),
+                    };
+                }
+                if ui.button("Play").clicked() {
+                    session.playtesting = true;
+                    state.set(AppState::Game).unwrap();
+                }
+                if ui.button("Back").clicked() {
+                    state.set(AppState::Menu).unwrap();
+                }
+            });
+            if !session.status.is_empty() {
+                ui.label(session.status.as_str());
+            }
+        });
+    if level != options.level {
+        options.level = level;
+        session.status.clear();
+        state.overwrite_restart();
+    }
+}
+
+/// Writes the map over its file, after making sure it can be read back.
+/// Tiled maps are written next to the original as `.chazmap`, since that is the only format we can write.
+fn save(asset_folder: &str, level: &LevelInfo, map: &Map, physics_config: &PhysicsConfig, difficulty: Difficulty) -> Result<String, String> {
+    let text = map.to_string();
+    let path = Path::new(&level.map).with_extension("chazmap");
+    let file = path.display().to_string();
+    read_map(&file, &text, None).map_err(|errors| errors.to_string())?;
+    write(asset_folder, &path, &text)?;
+    let mut status = format!("Saved {}", file);
+    if file != level.map {
+        status += &format!("\nPoint the level manifest at it to use it instead of {}", level.map);
+    }
+    for warning in lint(map, physics_config, &level.id, difficulty) {
+        status += &format!("\nWarning: {}", warning);
+    }
+    Ok(status)
+}
+
+/// `path` is relative to the asset folder.
+#[cfg(not(target_arch = "wasm32"))]
+fn write(asset_folder: &str, path: &Path, text: &str) -> Result<(), String> {
+    std::fs::write(crate::asset_files::resolve(asset_folder, path), text).map_err(|e| e.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(_asset_folder: &str, path: &Path, text: &str) -> Result<(), String> {
+    // there is no file system to write to, but the map can still be copied from the console
+    crate::console_log!("{}:\n{}", path.display(), text);
+    Ok(())
+}
+
+/// The tile under the mouse, if it is inside the map.
+fn cursor_tile(windows: &Windows, camera: &Transform, map: &Map) -> Option<(usize, usize)> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    let window_size = Vec2::new(window.width(), window.height());
+    let position = camera.translation.truncate() + cursor - window_size / 2.0;
+    let tile = ((position - map.tile_position(0, 0, 0.0).truncate()) / Tile::SIZE).round();
+    if tile.x < 0.0 || tile.y < 0.0 {
+        return None;
+    }
+    let (i, j) = (tile.x as usize, tile.y as usize);
+    if i < map.width() && j < map.height() {
+        Some((i, j))
+    } else {
+        None
+    }
+}
+
+fn paint(
+    mut egui_context: ResMut<EguiContext>,
+    mouse: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    camera_query: Query<&Transform, With<MainCamera>>,
+    options: Res<Options>,
+    levels: Res<Levels>,
+    mut maps: ResMut<Assets<Map>>,
+    mut session: ResMut<EditorSession>,
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    sprite_handles: Res<SpriteHandles>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    mut textures: ResMut<Assets<Image>>,
+) {
+    if egui_context.ctx_mut().wants_pointer_input() {
+        return;
+    }
+    let tile = if mouse.pressed(MouseButton::Left) {
+        session.brush
+    } else if mouse.pressed(MouseButton::Right) {
+        Tile::Empty
+    } else {
+        return;
+    };
+    let handle = match levels.map(&options.level) {
+        Some(handle) => handle,
+        None => return,
+    };
+    let cell = maps.get(handle)
+        .and_then(|map| cursor_tile(&windows, camera_query.single(), map).filter(|&(i, j)| map[i][j] != tile));
+    let (i, j) = match cell {
+        Some(cell) => cell,
+        None => return,
+    };
+    // only once something changes, as getting the map mutably reloads the level
+    let map = match maps.get_mut(handle) {
+        Some(map) => map,
+        None => return,
+    };
+    let mut changed = vec![(i, j)];
+    if let Tile::Player | Tile::Rival = tile {
+        // there can only be one of each
+        changed.extend(map.iter().filter(|&(i, j)| map[i][j] == tile));
+        for &(i, j) in changed.iter() {
+            map.set(i, j, Tile::Empty);
+        }
+    }
+    map.set(i, j, tile);
+
+    // the ground around a tile changes its image too
+    let mut spawn = |name| {
+        spawn(
+            name,
+            &sprite_handles,
+            &mut texture_atlases,
+            &mut textures,
+        )
+    };
+    for (x, y) in changed {
+        for i in x.saturating_sub(1)..=(x + 1).min(map.width() - 1) {
+            for j in y.saturating_sub(1)..=(y + 1).min(map.height() - 1) {
+                respawn_tile(i, j, map, &mut session.tiles, &mut commands, &asset_server, &mut spawn);
+            }
+        }
+    }
+}
+
+fn move_camera(
+    time: Res<Time>,
+    input: Res<Input<KeyCode>>,
+    mut query: Query<&mut Transform, With<MainCamera>>,
+) {
+    const SPEED: f32 = 400.0;
+    let mut direction = Vec3::ZERO;
+    if input.pressed(KeyCode::Left) {
+        direction.x -= 1.0;
+    }
+    if input.pressed(KeyCode::Right) {
+        direction.x += 1.0;
+    }
+    if input.pressed(KeyCode::Down) {
+        direction.y -= 1.0;
+    }
+    if input.pressed(KeyCode::Up) {
+        direction.y += 1.0;
+    }
+    for mut transform in query.iter_mut() {
+        transform.translation += direction * SPEED * time.delta_seconds();
+    }
+}
+
+fn cleanup(
+    mut commands: Commands,
+    mut session: ResMut<EditorSession>,
+    query: Query<Entity, With<EditorTile>>,
+) {
+    for id in query.iter() {
+        commands.entity(id).despawn();
+    }
+    session.tiles.clear();
+}
+
+fn end_playtest(mut session: ResMut<EditorSession>) {
+    session.playtesting = false;
+}
@@ -0,0 +1,158 @@
+use bevy::prelude::*;
+
+use crate::camera::MainCamera;
+use crate::options::{Difficulty, Options};
+
+use super::health::Health;
+use super::hitbox::{Collider, CollisionEvent, Layers};
+use super::levels::Levels;
+use super::map::Map;
+use super::outcome::{GameOutcome, Outcome};
+use super::player::{Character, Player};
+use super::velocity::Velocity;
+
+/// A flag that becomes the `SpawnPoint` of the characters touching it.
+#[derive(Component, Default)]
+pub struct Checkpoint {
+    /// The player touched it, which only changes how it looks.
+    pub reached: bool,
+}
+
+/// Where a character comes back after dying: where it started, or the last checkpoint it touched.
+#[derive(Clone, Component, Copy, Debug, Default)]
+pub struct SpawnPoint(pub Vec3);
+
+/// How many times a character can die before the level ends for it, counting the life being played.
+#[derive(Clone, Component, Copy, Debug, PartialEq)]
+pub struct Lives(pub u8);
+
+impl Lives {
+    pub fn new(difficulty: Difficulty) -> Self {
+        Lives(match difficulty {
+            Difficulty::Training => 9,
+            Difficulty::Easy => 5,
+            Difficulty::Medium => 3,
+            Difficulty::Hard | Difficulty::Zatoichi => 1,
+        })
+    }
+}
+
+impl Default for Lives {
+    fn default() -> Self {
+        Lives::new(Difficulty::default())
+    }
+}
+
+pub fn reach_checkpoint(
+    mut collisions: EventReader<CollisionEvent>,
+    mut checkpoint_query: Query<(&Collider, &Transform, &mut Checkpoint)>,
+    mut character_query: Query<(&mut SpawnPoint, &Transform, Option<&Player>)>,
+) {
+    for collision in collisions.iter() {
+        let (position, mut checkpoint) = match checkpoint_query.get_mut(collision.b) {
+            Ok((collider, transform, checkpoint)) if collider.layer == Layers::CHECKPOINT => (transform.translation, checkpoint),
+            _ => continue,
+        };
+        if let Ok((mut spawn_point, transform, player)) = character_query.get_mut(collision.a) {
+            spawn_point.0 = position.truncate().extend(transform.translation.z);
+            checkpoint.reached |= player.is_some();
+        }
+    }
+}
+
+/// Characters die when they have no health left or fall off the map. With a life to spare they come back
+/// at their `SpawnPoint` right away, otherwise the player loses the level and a rival is out of the race.
+pub fn lose_life(
+    mut outcome: ResMut<GameOutcome>,
+    mut commands: Commands,
+    levels: Res<Levels>,
+    maps: Res<Assets<Map>>,
+    options: Res<Options>,
+    mut query: Query<(Entity, &mut Lives, &mut Health, &SpawnPoint, &mut Character, &mut Transform, &mut Velocity, Option<&Player>)>,
+) {
+    let fall_limit = match levels.map(&options.level).and_then(|handle| maps.get(handle)) {
+        Some(map) => map.fall_limit(),
+        None => return,
+    };
+    for (entity, mut lives, mut health, spawn_point, mut character, mut transform, mut velocity, player) in query.iter_mut() {
+        let cause = if health.is_dead() {
+            Outcome::KilledByEnemy
+        } else if transform.translation.y < fall_limit {
+            Outcome::Fell
+        } else {
+            continue;
+        };
+        if lives.0 > 1 {
+            lives.0 -= 1;
+            health.revive();
+            transform.translation = spawn_point.0;
+            *velocity = Velocity::default();
+            *character = Character::with_skin(character.skin);
+        } else if player.is_some() {
+            outcome.decide(cause);
+        } else {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+/// Flags the player has not reached yet are greyed out.
+pub fn update_checkpoint_flags(mut query: Query<(&Checkpoint, &mut TextureAtlasSprite)>) {
+    for (checkpoint, mut sprite) in query.iter_mut() {
+        sprite.color = if checkpoint.reached { Color::WHITE } else { Color::GRAY };
+    }
+}
+
+/// The lives left, in the top right corner of the screen.
+#[derive(Component)]
+pub struct LivesCounter;
+
+pub fn spawn_lives_counter(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    options: Res<Options>,
+    asset_server: Res<AssetServer>,
+    camera_query: Query<Entity, With<MainCamera>>,
+) {
+    let window = windows.get_primary().unwrap();
+    let text_style = TextStyle {
+        font: asset_server.load("kenney-fonts/Fonts/Kenney Blocks.ttf"),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+    let text_alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Right,
+    };
+    let text = lives_text(Lives::new(options.difficulty));
+    commands.entity(camera_query.single()).with_children(|camera| {
+        camera
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(text, text_style, text_alignment),
+                transform: Transform::from_translation(Vec3::new(window.width() / 2.0 - 20.0, window.height() / 2.0 - 20.0, -10.0)),
+                ..Default::default()
+            })
+            .insert(LivesCounter);
+    });
+}
+
+fn lives_text(lives: Lives) -> String {
+    format!("Lives {}", lives.0)
+}
+
+pub fn update_lives_counter(
+    player_query: Query<&Lives, (With<Player>, Changed<Lives>)>,
+    mut counter_query: Query<&mut Text, With<LivesCounter>>,
+) {
+    for lives in player_query.iter() {
+        for mut text in counter_query.iter_mut() {
+            text.sections[0].value = lives_text(*lives);
+        }
+    }
+}
+
+pub fn despawn_lives_counter(mut commands: Commands, query: Query<Entity, With<LivesCounter>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
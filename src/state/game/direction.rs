@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-#[derive(Clone, Component, Copy, Default, PartialEq)]
+#[derive(Clone, Component, Copy, Debug, Default, PartialEq)]
 pub enum Direction {
     Left,
     #[default]
@@ -36,6 +36,27 @@ impl Direction {
     }
 }
 
+impl std::str::FromStr for Direction {
+
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Direction, Self::Err> {
+        match input {
+            "Left" => Ok(Direction::Left),
+            "Right" => Ok(Direction::Right),
+            "Up" => Ok(Direction::Up),
+            "Down" => Ok(Direction::Down),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
 impl From<Direction> for f32 {
     fn from(direction: Direction) -> Self {
         match direction {
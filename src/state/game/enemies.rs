@@ -1,7 +1,47 @@
+use std::collections::HashSet;
+
 use bevy::prelude::*;
 
+use super::player::Player;
+
 #[derive(Component)]
 pub struct Jeremy;
 
 #[derive(Component)]
 pub struct Blocky;
+
+/// How an enemy moves back and forth from its initial position.
+#[derive(Clone, Component, Copy, Debug, PartialEq)]
+pub struct Patrol {
+    pub speed: f32,
+    pub amplitude: f32,
+}
+
+impl Patrol {
+    pub const JEREMY: Patrol = Patrol { speed: 20.0, amplitude: 20.0 };
+    /// The speed is used going up, Blocky always falls at `Blocky::FALL_SPEED`.
+    pub const BLOCKY: Patrol = Patrol { speed: 48.0, amplitude: 96.0 };
+}
+
+impl Blocky {
+    pub const FALL_SPEED: f32 = 120.0;
+}
+
+/// The enemies a character stomped. They are gone for that character only: a rival replaying a run
+/// meets the enemies the player met when recording it, whatever the player stomps now.
+#[derive(Clone, Component, Debug, Default)]
+pub struct Stomped(pub HashSet<Entity>);
+
+/// Enemies the player stomped are not drawn, though they still move for the rival.
+pub fn hide_stomped_enemies(
+    player_query: Query<&Stomped, (With<Player>, Changed<Stomped>)>,
+    mut enemy_query: Query<&mut Visibility>,
+) {
+    for stomped in player_query.iter() {
+        for &enemy in stomped.0.iter() {
+            if let Ok(mut visibility) = enemy_query.get_mut(enemy) {
+                visibility.is_visible = false;
+            }
+        }
+    }
+}
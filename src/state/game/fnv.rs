@@ -0,0 +1,21 @@
+/// The FNV-1a hash, for hashes that are saved:
+/// unlike `std::hash`, it is the same on every platform and version of Rust.
+pub struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf29ce484222325)
+    }
+}
+
+impl Fnv1a {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+    pub fn finish(&self) -> u64 {
+        self.0
+    }
+}
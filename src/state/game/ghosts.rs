@@ -0,0 +1,178 @@
+use std::fmt;
+
+use bevy::prelude::*;
+
+use super::levels::Levels;
+use super::map::{Map, RivalLevelPositions, RivalPositions};
+use super::parse_error::MapParseErrors;
+use super::physics::Physics;
+use super::positions::{read_positions_from_file, GhostInfo, Interpolation, Positions, Sample};
+use super::replay::{read_replay_from_file, Replay};
+
+use crate::log::console_log;
+use crate::storage;
+
+/// A run the rival can play back.
+/// Old runs are sampled positions, new ones are the inputs of every tick.
+#[derive(Clone, Debug)]
+pub enum Ghost {
+    Positions(Positions),
+    Replay(Replay),
+}
+
+impl Default for Ghost {
+    fn default() -> Self {
+        Ghost::Positions(Positions::default())
+    }
+}
+
+impl Ghost {
+    pub fn info(&self) -> &GhostInfo {
+        match self {
+            Ghost::Positions(positions) => &positions.info,
+            Ghost::Replay(replay) => &replay.info,
+        }
+    }
+    /// Where the run starts, if it has anything in it.
+    pub fn start(&self) -> Option<Vec2> {
+        match self {
+            Ghost::Positions(positions) => positions.values.front().map(|sample| sample.position.truncate()),
+            Ghost::Replay(replay) => (!replay.inputs.is_empty()).then(|| replay.start),
+        }
+    }
+    /// Whether the run can be played back on `map`. Replays also need the physics they were recorded with,
+    /// `physics` being those of the difficulty they were recorded on, sampled positions go where they went anyway.
+    pub fn fits(&self, map: &Map, physics: &Physics) -> bool {
+        match self {
+            Ghost::Positions(positions) => positions.info.fits(map),
+            Ghost::Replay(replay) => replay.info.fits(map) && replay.info.physics_hash == Some(physics.hash()),
+        }
+    }
+}
+
+impl fmt::Display for Ghost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ghost::Positions(positions) => positions.fmt(f),
+            Ghost::Replay(replay) => replay.fmt(f),
+        }
+    }
+}
+
+/// How far a rival is in playing back its `Positions`.
+/// The position is worked out from the time alone, so playback can jump anywhere in the run.
+#[derive(Component, Debug, Default)]
+pub struct GhostPlayback {
+    /// Seconds since the first sample.
+    pub elapsed: f32,
+    pub interpolation: Interpolation,
+}
+
+impl GhostPlayback {
+    pub fn advance(&mut self, seconds: f32) {
+        self.elapsed += seconds;
+    }
+    /// Jumps to `time` seconds into the run, e.g. to scrub through it while viewing a replay.
+    pub fn seek(&mut self, positions: &Positions, time: f32) {
+        self.elapsed = time.clamp(0.0, positions.duration());
+    }
+    pub fn finished(&self, positions: &Positions) -> bool {
+        self.elapsed >= positions.duration()
+    }
+    pub fn sample(&self, positions: &Positions) -> Option<Sample> {
+        positions.sample_at(self.elapsed, self.interpolation)
+    }
+}
+
+/// Reads a positions file, or a replay file if its `version` entry says so.
+pub fn read_ghost(file: &str, text: &str) -> Result<Ghost, MapParseErrors> {
+    let version = text.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(key, _)| key.trim() == "version")
+        .and_then(|(_, value)| value.trim().parse::<u32>().ok());
+    match version {
+        Some(version) if version >= Replay::FIRST_VERSION => read_replay_from_file(file, text).map(Ghost::Replay),
+        _ => read_positions_from_file(file, text).map(Ghost::Positions),
+    }
+}
+
+/// Stolen rival runs are stored like the files in `assets/positions`.
+fn storage_key(level: &str) -> String {
+    format!("ghosts/{}_positions.txt", level)
+}
+
+/// Brings back the runs stolen in earlier sessions, once the levels are known.
+pub fn load_stolen_positions(levels: Res<Levels>, mut rival_positions: ResMut<RivalPositions>) {
+    for level in levels.iter() {
+        let key = storage_key(&level.id);
+        let text = match storage::load(&key) {
+            Some(text) => text,
+            None => continue,
+        };
+        match read_ghost(&key, &text) {
+            Ok(ghost) => {
+                rival_positions.0.insert(level.id.clone(), RivalLevelPositions::Stolen(ghost));
+            },
+            Err(errors) => console_log!("Ignoring the stolen rival of level {}:\n{}", level.id, errors),
+        }
+    }
+}
+
+pub fn save_stolen_positions(level: &str, ghost: &Ghost) {
+    if let Err(error) = storage::save(&storage_key(level), &ghost.to_string()) {
+        console_log!("Could not save the stolen rival of level {}: {}", level, error);
+    }
+}
+
+/// Gives a level its original rival back.
+pub fn reset_stolen_positions(rival_positions: &mut RivalPositions, level: &str) {
+    rival_positions.0.insert(level.to_string(), RivalLevelPositions::HardCoded);
+    storage::remove(&storage_key(level));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::direction::Direction;
+    use super::super::map::read_map;
+    use super::super::player::Animation;
+
+    #[test]
+    fn seeking_stays_within_the_run() {
+        let mut positions = Positions::default();
+        positions.values.extend((0..4).map(|i| Sample {
+            position: Vec3::new(10.0 * i as f32, 0.0, 0.0),
+            animation: Animation::Walking,
+            facing: Direction::Right,
+        }));
+        let mut playback = GhostPlayback::default();
+        playback.seek(&positions, 1.5 * positions.interval());
+        assert!((playback.sample(&positions).unwrap().position.x - 15.0).abs() < 1e-3);
+        assert!(!playback.finished(&positions));
+        playback.seek(&positions, 10.0);
+        assert_eq!(playback.elapsed, positions.duration());
+        assert!(playback.finished(&positions));
+        playback.seek(&positions, -1.0);
+        assert_eq!(playback.sample(&positions).unwrap().position, Vec3::ZERO);
+    }
+
+    #[test]
+    fn replays_only_fit_the_physics_they_were_recorded_with() {
+        let map = read_map("test", "Ground Player\nGround Win\n", None).unwrap();
+        let physics = Physics::default();
+        let heavier = Physics { gravity: 600.0, ..physics };
+        let info = GhostInfo { map_hash: Some(map.hash()), physics_hash: Some(physics.hash()), ..Default::default() };
+        let replay = Ghost::Replay(Replay { info: info.clone(), ..Default::default() });
+        assert!(replay.fits(&map, &physics));
+        assert!(!replay.fits(&map, &heavier));
+        let unknown = Ghost::Replay(Replay { info: GhostInfo { physics_hash: None, ..info.clone() }, ..Default::default() });
+        assert!(!unknown.fits(&map, &physics));
+        let positions = Ghost::Positions(Positions { info, ..Default::default() });
+        assert!(positions.fits(&map, &heavier));
+    }
+
+    #[test]
+    fn an_empty_run_has_no_start() {
+        assert_eq!(Ghost::default().start(), None);
+    }
+}
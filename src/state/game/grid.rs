@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+
+use super::hitbox::{Collider, CollisionEvent, Hitbox, Layers};
+use super::map::{Map, Tile};
+
+/// The ground hitboxes of the level, by tile, so finding the ground around a character
+/// costs the same however big the map is. Ground never moves, so this is built once per level.
+#[derive(Default)]
+pub struct GroundGrid {
+    origin: Vec3,
+    width: usize,
+    height: usize,
+    /// Column by column, like the tiles of a `Map`.
+    cells: Vec<Option<Hitbox>>,
+}
+
+impl GroundGrid {
+    pub fn new(map: &Map) -> Self {
+        let mut cells = vec![None; map.width() * map.height()];
+        for (i, j) in map.iter() {
+            if let Some(tile_info) = map.get_tile_info(i, j) {
+                if tile_info.tile_type == Tile::Ground {
+                    cells[i * map.height() + j] = tile_info.hitbox;
+                }
+            }
+        }
+        GroundGrid {
+            origin: map.tile_position(0, 0, 0.0),
+            width: map.width(),
+            height: map.height(),
+            cells,
+        }
+    }
+
+    /// The tile `position` is in, which may be outside of the map.
+    pub fn cell(&self, position: Vec3) -> (i64, i64) {
+        let cell = ((position - self.origin) / Tile::SIZE).round();
+        (cell.x as i64, cell.y as i64)
+    }
+
+    /// The ground tiles at most `radius` tiles away from the one at `position`, with their positions.
+    pub fn near(&self, position: Vec3, radius: i64) -> impl Iterator<Item = (&Hitbox, Vec3)> + '_ {
+        let (x, y) = self.cell(position);
+        let columns = (x - radius).max(0)..(x + radius + 1).min(self.width as i64);
+        let rows = (y - radius).max(0)..(y + radius + 1).min(self.height as i64);
+        columns
+            .flat_map(move |i| rows.clone().map(move |j| (i as usize, j as usize)))
+            .filter_map(move |(i, j)| {
+                let hitbox = self.cells[i * self.height + j].as_ref()?;
+                Some((hitbox, self.origin + Tile::SIZE * Vec3::new(i as f32, j as f32, 0.0)))
+            })
+    }
+}
+
+/// The colliders of the level, in every cell their hitbox covers, so two hitboxes that touch share a cell
+/// whatever their sizes. They move, so this is filled again every tick, after `TickStep::Movement`.
+#[derive(Default)]
+pub struct ColliderGrid {
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+}
+
+impl ColliderGrid {
+    /// Twice the size of a tile, so a tile-sized hitbox covers at most four cells.
+    const CELL_SIZE: f32 = 2.0 * Tile::SIZE;
+
+    fn cells(hitbox: &Hitbox, position: Vec3) -> impl Iterator<Item = (i32, i32)> {
+        let center = (position + hitbox.relative_position).truncate();
+        let min = ((center - hitbox.size / 2.0) / Self::CELL_SIZE).floor();
+        let max = ((center + hitbox.size / 2.0) / Self::CELL_SIZE).floor();
+        (min.x as i32..=max.x as i32).flat_map(move |i| (min.y as i32..=max.y as i32).map(move |j| (i, j)))
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.values_mut().for_each(Vec::clear);
+    }
+
+    pub fn insert(&mut self, entity: Entity, hitbox: &Hitbox, position: Vec3) {
+        for cell in Self::cells(hitbox, position) {
+            self.cells.entry(cell).or_default().push(entity);
+        }
+    }
+
+    /// The colliders that might touch `hitbox` at `position`, each one once.
+    pub fn near(&self, hitbox: &Hitbox, position: Vec3) -> impl Iterator<Item = Entity> {
+        let mut near = Self::cells(hitbox, position)
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        near.sort_unstable();
+        near.dedup();
+        near.into_iter()
+    }
+}
+
+pub fn update_collider_grid(mut grid: ResMut<ColliderGrid>, query: Query<(Entity, &Collider, &Transform)>) {
+    grid.clear();
+    for (entity, collider, transform) in query.iter() {
+        grid.insert(entity, &collider.hitbox, transform.translation);
+    }
+}
+
+/// Sends a `CollisionEvent` for every collider touching one on a layer of its mask.
+pub fn detect_collisions(
+    grid: Res<ColliderGrid>,
+    query: Query<(Entity, &Collider, &Transform)>,
+    mut events: EventWriter<CollisionEvent>,
+) {
+    for (a, collider, transform) in query.iter() {
+        if collider.mask == Layers::NONE {
+            continue;
+        }
+        for b in grid.near(&collider.hitbox, transform.translation) {
+            let (other, other_transform) = match query.get(b) {
+                Ok((_, other, other_transform)) if a != b && collider.mask.intersects(other.layer) => (other, other_transform),
+                _ => continue,
+            };
+            if let Some(collision) = collider.hitbox.collide(&transform.translation, &other.hitbox, &other_transform.translation) {
+                events.send(CollisionEvent { a, b, side: collision.collision_type, overlap: collision.overlap });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_collider_wider_than_the_cells_is_found_from_its_far_end() {
+        let wall = Hitbox { relative_position: Vec3::ZERO, size: Vec2::new(10.0 * Tile::SIZE, Tile::SIZE) };
+        let tile = Hitbox { relative_position: Vec3::ZERO, size: Vec2::splat(Tile::SIZE) };
+        let mut grid = ColliderGrid::default();
+        grid.insert(Entity::from_raw(0), &wall, Vec3::ZERO);
+        let end = Vec3::new(5.0 * Tile::SIZE, 0.0, 0.0);
+        assert_eq!(grid.near(&tile, end).collect::<Vec<_>>(), vec![Entity::from_raw(0)]);
+        assert!(wall.collide(&Vec3::ZERO, &tile, &end).is_some());
+        assert_eq!(grid.near(&tile, end + Vec3::new(2.0 * Tile::SIZE, 0.0, 0.0)).count(), 0);
+    }
+}
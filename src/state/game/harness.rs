@@ -0,0 +1,154 @@
+//! Plays a level without a window, a renderer or audio, one tick at a time, for tests and the linter.
+
+use bevy::asset::AssetPlugin;
+use bevy::prelude::*;
+
+use super::levels::{LevelInfo, Levels};
+use super::map::{read_map, Map};
+use super::outcome::GameOutcome;
+use super::parse_error::MapParseErrors;
+use super::physics::{Physics, PhysicsConfig};
+use super::player::{Player, Rival};
+use super::tick::GameTicks;
+use super::{load_level, GameSimulation};
+
+pub use super::checkpoint::Lives;
+pub use super::health::Health;
+pub use super::outcome::Outcome;
+pub use super::replay::InputFrame;
+pub use super::stats::LevelStats;
+
+use crate::background::CurrentBackground;
+use crate::options::Options;
+
+pub struct Harness {
+    pub app: App,
+}
+
+impl Harness {
+    /// Loads `map` as the level in `options`, like entering `AppState::Game` would.
+    pub fn new(map: Map, options: Options) -> Self {
+        Self::with_physics(map, options, Physics::default())
+    }
+
+    /// Like `new`, with `physics` for every level and difficulty instead of the defaults.
+    pub fn with_physics(map: Map, options: Options, physics: Physics) -> Self {
+        Self::with_physics_config(map, options, PhysicsConfig { base: physics, ..Default::default() })
+    }
+
+    /// Like `new`, with the physics of the level and difficulty in `options` taken from `physics_config`.
+    pub fn with_physics_config(map: Map, options: Options, physics_config: PhysicsConfig) -> Self {
+        let mut app = App::new();
+        app
+            .add_plugins(MinimalPlugins)
+            .add_plugin(AssetPlugin)
+            .init_resource::<CurrentBackground>()
+            .insert_resource(physics_config)
+            .add_plugin(GameSimulation);
+        let handle = app.world.resource_mut::<Assets<Map>>().add(map);
+        {
+            let mut levels = app.world.resource_mut::<Levels>();
+            levels.levels.push(LevelInfo {
+                id: options.level.clone(),
+                name: String::new(),
+                map: String::new(),
+                order: 0,
+                hidden: false,
+            });
+            levels.maps.insert(options.level.clone(), handle);
+        }
+        app.insert_resource(options);
+        SystemStage::single_threaded()
+            .with_system(load_level)
+            .run(&mut app.world);
+        Harness { app }
+    }
+
+    /// Loads the text of a map file, with no rival, and the default options.
+    pub fn from_text(map: &str) -> Result<Self, MapParseErrors> {
+        Ok(Self::new(read_map("harness", map, None)?, Options::default()))
+    }
+
+    fn set_input(&mut self, input: InputFrame) {
+        let world = &mut self.app.world;
+        for mut frame in world.query_filtered::<&mut InputFrame, With<Player>>().iter_mut(world) {
+            *frame = input;
+        }
+    }
+
+    /// Runs one tick, with the player doing `input`.
+    pub fn tick(&mut self, input: InputFrame) {
+        self.set_input(input);
+        self.app.world.resource_scope(|world, mut ticks: Mut<GameTicks>| ticks.tick(world));
+    }
+
+    /// Runs a frame that took `seconds`, with the player doing `input`,
+    /// so as many ticks run as would in the game at that frame rate.
+    pub fn frame(&mut self, seconds: f32, input: InputFrame) {
+        self.set_input(input);
+        self.app.world.resource_scope(|world, mut ticks: Mut<GameTicks>| ticks.advance(world, seconds));
+    }
+
+    /// Runs `(ticks, input)` pairs in order, until the outcome is decided or the script ends.
+    pub fn play(&mut self, script: &[(usize, InputFrame)]) -> Option<&Outcome> {
+        for &(ticks, input) in script {
+            for _ in 0..ticks {
+                if self.app.world.resource::<GameOutcome>().is_decided() {
+                    return self.outcome();
+                }
+                self.tick(input);
+            }
+        }
+        self.outcome()
+    }
+
+    pub fn outcome(&self) -> Option<&Outcome> {
+        self.app.world.resource::<GameOutcome>().get()
+    }
+
+    pub fn player_position(&mut self) -> Vec3 {
+        let world = &mut self.app.world;
+        world.query_filtered::<&Transform, With<Player>>()
+            .iter(world)
+            .next()
+            .expect("Every map has a Player tile")
+            .translation
+    }
+
+    pub fn stats(&self) -> LevelStats {
+        *self.app.world.resource::<LevelStats>()
+    }
+
+    pub fn player_lives(&mut self) -> Lives {
+        let world = &mut self.app.world;
+        *world.query_filtered::<&Lives, With<Player>>()
+            .iter(world)
+            .next()
+            .expect("Every map has a Player tile")
+    }
+
+    pub fn player_health(&mut self) -> Health {
+        let world = &mut self.app.world;
+        *world.query_filtered::<&Health, With<Player>>()
+            .iter(world)
+            .next()
+            .expect("Every map has a Player tile")
+    }
+    /// Where the rival is, if the level has one racing.
+    pub fn rival_position(&mut self) -> Option<Vec3> {
+        let world = &mut self.app.world;
+        world.query_filtered::<&Transform, With<Rival>>()
+            .iter(world)
+            .next()
+            .map(|transform| transform.translation)
+    }
+
+    /// Only a rival replaying a run has lives.
+    pub fn rival_lives(&mut self) -> Option<Lives> {
+        let world = &mut self.app.world;
+        world.query_filtered::<&Lives, With<Rival>>()
+            .iter(world)
+            .next()
+            .copied()
+    }
+}
@@ -0,0 +1,143 @@
+use bevy::prelude::*;
+
+use crate::camera::MainCamera;
+use crate::options::{Difficulty, Options};
+use crate::sprite::{SpriteType, SpriteTypeStates, SPRITES};
+
+use super::player::Player;
+use super::tick::TICK_SECONDS;
+
+/// How many more enemy hits a character can take, in half hearts.
+#[derive(Clone, Component, Copy, Debug, PartialEq)]
+pub struct Health {
+    pub current: u8,
+    pub max: u8,
+    /// Ticks left until enemies can hurt the character again.
+    pub invulnerable: u32,
+}
+
+impl Health {
+    /// How long enemies can not hurt a character after it was hit.
+    pub const INVULNERABILITY_TIME: f32 = 1.0;
+
+    pub fn new(difficulty: Difficulty) -> Self {
+        let max = match difficulty {
+            Difficulty::Training => 10,
+            Difficulty::Easy => 6,
+            Difficulty::Medium => 4,
+            Difficulty::Hard => 2,
+            Difficulty::Zatoichi => 1,
+        };
+        Health { current: max, max, invulnerable: 0 }
+    }
+    fn invulnerability_ticks() -> u32 {
+        (Self::INVULNERABILITY_TIME / TICK_SECONDS).round() as u32
+    }
+    pub fn is_invulnerable(&self) -> bool {
+        self.invulnerable > 0
+    }
+    pub fn is_dead(&self) -> bool {
+        self.current == 0
+    }
+    /// Takes half a heart, unless the last hit was too recent. Returns whether it did.
+    pub fn hurt(&mut self) -> bool {
+        if self.is_invulnerable() {
+            return false;
+        }
+        self.current = self.current.saturating_sub(1);
+        self.invulnerable = Self::invulnerability_ticks();
+        true
+    }
+    /// Back to full health, and invulnerable for a bit so the character is not hit right away.
+    pub fn revive(&mut self) {
+        self.current = self.max;
+        self.invulnerable = Self::invulnerability_ticks();
+    }
+}
+
+impl Default for Health {
+    fn default() -> Self {
+        Health::new(Difficulty::default())
+    }
+}
+
+pub fn update_invulnerability(mut query: Query<&mut Health>) {
+    for mut health in query.iter_mut() {
+        if health.is_invulnerable() {
+            health.invulnerable -= 1;
+        }
+    }
+}
+
+/// Shows and hides invulnerable characters, a few times a second.
+pub fn blink(mut query: Query<(&Health, &mut Visibility)>) {
+    const BLINK_TICKS: u32 = 6;
+    for (health, mut visibility) in query.iter_mut() {
+        visibility.is_visible = health.invulnerable / BLINK_TICKS % 2 == 0;
+    }
+}
+
+/// One of the hearts in the top left corner of the screen, counting from the left.
+#[derive(Component)]
+pub struct Heart(u8);
+
+impl Heart {
+    const SPACING: f32 = 20.0;
+
+    fn sprite(&self, health: &Health) -> &'static str {
+        let state = match health.current.saturating_sub(2 * self.0) {
+            0 => SpriteTypeStates::Empty,
+            1 => SpriteTypeStates::Half,
+            _ => SpriteTypeStates::Full,
+        };
+        SPRITES[&SpriteType::Heart][&state]
+    }
+}
+
+pub fn spawn_hearts(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    options: Res<Options>,
+    asset_server: Res<AssetServer>,
+    camera_query: Query<Entity, With<MainCamera>>,
+) {
+    let window = windows.get_primary().unwrap();
+    let corner = Vec3::new(
+        -window.width() / 2.0 + Heart::SPACING,
+        window.height() / 2.0 - Heart::SPACING,
+        -10.0,
+    );
+    let health = Health::new(options.difficulty);
+    commands.entity(camera_query.single()).with_children(|parent| {
+        for i in 0..(health.max + 1) / 2 {
+            let heart = Heart(i);
+            parent
+                .spawn_bundle(SpriteBundle {
+                    texture: asset_server.get_handle(heart.sprite(&health)),
+                    transform: Transform::from_translation(corner + Vec3::X * Heart::SPACING * i as f32),
+                    ..Default::default()
+                })
+                .insert(heart);
+        }
+    });
+}
+
+pub fn update_hearts(
+    asset_server: Res<AssetServer>,
+    player_query: Query<&Health, With<Player>>,
+    mut heart_query: Query<(&Heart, &mut Handle<Image>)>,
+) {
+    let health = match player_query.get_single() {
+        Ok(health) => health,
+        Err(_) => return,
+    };
+    for (heart, mut image) in heart_query.iter_mut() {
+        *image = asset_server.get_handle(heart.sprite(health));
+    }
+}
+
+pub fn despawn_hearts(mut commands: Commands, query: Query<Entity, With<Heart>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,9 +1,6 @@
-use bevy::prelude::*;
+use std::ops::BitOr;
 
-//TODO: derive this for all hitboxes for comfort
-trait Collide {
-    fn collide<T: Collide>(&self, other: &T) -> Option<CollisionType>;
-}
+use bevy::prelude::*;
 
 #[derive(Clone, Default)]
 pub struct Hitbox {
@@ -11,6 +8,8 @@ pub struct Hitbox {
     pub size: Vec2,
 }
 
+/// The side of a hitbox that touched the other one.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CollisionType {
     Left,
     Right,
@@ -23,7 +22,63 @@ pub struct Collision {
     pub overlap: f32,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+impl Axis {
+    /// The index of the axis in a `Vec2` or `Vec3`.
+    pub fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+        }
+    }
+    fn other(self) -> Axis {
+        match self {
+            Axis::X => Axis::Y,
+            Axis::Y => Axis::X,
+        }
+    }
+}
+
+/// Where a moving hitbox first touches another one.
+pub struct Contact {
+    /// From 0, touching from the start, to 1, touching at the end of the move.
+    pub time: f32,
+    /// The coordinate of the moving hitbox's position, along the axis of the move, when they touch.
+    pub position: f32,
+}
+
 impl Hitbox {
+    /// Hitboxes closer than this are touching, so walking over the seam between two tiles does not catch on either.
+    const CONTACT_TOLERANCE: f32 = 0.01;
+
+    /// Moves this hitbox, at `position`, by `distance` along `axis`, and finds where it first touches `other`,
+    /// however long the move, so fast characters cannot go through thin ground.
+    /// Hitboxes that already overlap are ignored: characters start on tiles of their own
+    /// and every move stops where they touch the ground, so they never end up inside it.
+    pub fn sweep(&self, position: &Vec3, axis: Axis, distance: f32, other: &Hitbox, other_position: &Vec3) -> Option<Contact> {
+        let (along, across) = (axis.index(), axis.other().index());
+        let a = (*position + self.relative_position).truncate();
+        let b = (*other_position + other.relative_position).truncate();
+        let reach = (self.size + other.size) / 2.0;
+        if distance == 0.0 || (a[across] - b[across]).abs() >= reach[across] - Self::CONTACT_TOLERANCE {
+            return None;
+        }
+        let contact = b[along] - reach[along] * distance.signum();
+        let gap = (contact - a[along]) * distance.signum();
+        if gap < -Self::CONTACT_TOLERANCE || gap > distance.abs() {
+            return None;
+        }
+        Some(Contact {
+            time: gap.max(0.0) / distance.abs(),
+            position: contact - self.relative_position[along],
+        })
+    }
+
     pub fn collide(&self, position: &Vec3, other: &Hitbox, other_position: &Vec3) -> Option<Collision> {
         Hitbox::inner_collide(
             *other_position + other.relative_position,
@@ -82,17 +137,57 @@ impl Hitbox {
     }
 }
 
-#[derive(Component, Default)]
-pub struct PlayerGroundHitbox(pub Hitbox);
+/// Kinds of hitboxes, to tell what should collide with what.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Layers(u8);
 
-#[derive(Component, Default)]
-pub struct GroundHitbox(pub Hitbox);
+impl Layers {
+    pub const NONE: Layers = Layers(0);
+    /// Characters with it in their mask are stopped by the tiles of the `GroundGrid`.
+    pub const GROUND: Layers = Layers(1 << 0);
+    pub const CHARACTER: Layers = Layers(1 << 1);
+    pub const ENEMY: Layers = Layers(1 << 2);
+    pub const WIN: Layers = Layers(1 << 3);
+    pub const CHECKPOINT: Layers = Layers(1 << 4);
+    pub const COLLECTIBLE: Layers = Layers(1 << 5);
+
+    /// Whether every layer of `other` is in these.
+    pub fn contains(self, other: Layers) -> bool {
+        self.0 & other.0 == other.0
+    }
+    pub fn intersects(self, other: Layers) -> bool {
+        self.0 & other.0 != 0
+    }
+}
 
-#[derive(Component, Default)]
-pub struct WinHitbox(pub Hitbox);
+impl BitOr for Layers {
+    type Output = Layers;
 
-#[derive(Component, Default)]
-pub struct PlayerEnemyHitbox(pub Hitbox);
+    fn bitor(self, other: Layers) -> Layers {
+        Layers(self.0 | other.0)
+    }
+}
 
-#[derive(Component, Default)]
-pub struct EnemyHitbox(pub Hitbox);
+/// A hitbox, on the `layer` it is in, and the `mask` of layers it wants to know it touched.
+/// Touching sends a `CollisionEvent`, except for the ground, which has no colliders and stops characters instead.
+#[derive(Clone, Component, Default)]
+pub struct Collider {
+    pub hitbox: Hitbox,
+    pub layer: Layers,
+    pub mask: Layers,
+}
+
+impl Collider {
+    pub fn new(hitbox: Hitbox, layer: Layers, mask: Layers) -> Self {
+        Collider { hitbox, layer, mask }
+    }
+}
+
+/// Entity `a`, which has `b`'s layer in its mask, touched `b` with its `side`, by `overlap`.
+#[derive(Clone, Debug)]
+pub struct CollisionEvent {
+    pub a: Entity,
+    pub b: Entity,
+    pub side: CollisionType,
+    pub overlap: f32,
+}
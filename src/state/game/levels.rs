@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::Handle;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+
+use super::map::{positions_path, read_map, Map};
+use super::tiled::read_tiled_map;
+
+pub const MANIFEST_PATH: &str = "maps/chaz.levels.ron";
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LevelInfo {
+    /// Identifies the level in the leaderboard and in the rival storage.
+    pub id: String,
+    pub name: String,
+    /// Path of the `.chazmap` file, relative to the assets folder.
+    pub map: String,
+    #[serde(default)]
+    pub order: i32,
+    /// Hidden levels are playable, but not listed in the level select.
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "0f3a2c7d-8b4e-4f61-b2d9-5e7a1c9d3f08"]
+pub struct LevelManifest {
+    pub levels: Vec<LevelInfo>,
+}
+
+/// Reads a manifest, with its levels in order.
+pub fn read_level_manifest(text: &str) -> Result<LevelManifest, ron::Error> {
+    let mut manifest: LevelManifest = ron::de::from_str(text)?;
+    manifest.levels.sort_by_key(|level| level.order);
+    Ok(manifest)
+}
+
+impl LevelInfo {
+    /// Reads the map of the level from the `assets` folder, like the game loads it, with its rival positions if any.
+    /// Each error is a line to show, with the file it is about.
+    pub fn read_map_from(&self, assets: &Path) -> Result<Map, Vec<String>> {
+        let read = |path: &Path| std::fs::read_to_string(path).map_err(|e| vec![format!("{}: {}", path.display(), e)]);
+        let map_path = assets.join(&self.map);
+        let file = map_path.display().to_string();
+        let map_text = read(&map_path)?;
+        let positions = positions_path(Path::new(&self.map))
+            .map(|path| assets.join(path))
+            .filter(|path| path.exists())
+            .map(|path| Ok::<_, Vec<String>>((path.display().to_string(), read(&path)?)))
+            .transpose()?;
+        let positions = positions.as_ref().map(|(path, text)| (path.as_str(), text.as_str()));
+        let map = if map_path.extension().map_or(false, |extension| extension == "chazmap") {
+            read_map(&file, &map_text, positions)
+        } else {
+            read_tiled_map(&file, &map_text, positions)
+        };
+        map.map_err(|errors| errors.0.iter().map(ToString::to_string).collect())
+    }
+}
+
+#[derive(Default)]
+pub struct LevelManifestLoader;
+
+impl AssetLoader for LevelManifestLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let manifest = read_level_manifest(std::str::from_utf8(bytes)?)?;
+            load_context.set_default_asset(LoadedAsset::new(manifest));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["levels.ron"]
+    }
+}
+
+/// Every level listed in the manifest, in order, with the handle of its map.
+#[derive(Default)]
+pub struct Levels {
+    pub manifest: Handle<LevelManifest>,
+    pub levels: Vec<LevelInfo>,
+    pub maps: HashMap<String, Handle<Map>>,
+}
+
+impl Levels {
+    pub fn iter(&self) -> impl Iterator<Item = &LevelInfo> + '_ {
+        self.levels.iter()
+    }
+    pub fn visible(&self) -> impl Iterator<Item = &LevelInfo> + '_ {
+        self.iter().filter(|level| !level.hidden)
+    }
+    pub fn get(&self, id: &str) -> Option<&LevelInfo> {
+        self.iter().find(|level| level.id == id)
+    }
+    pub fn map(&self, id: &str) -> Option<&Handle<Map>> {
+        self.maps.get(id)
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+use bevy::prelude::*;
+
+use crate::options::{Difficulty, Options};
+
+use super::direction::Direction;
+use super::ghosts::Ghost;
+use super::grid::GroundGrid;
+use super::harness::Harness;
+use super::hitbox::Hitbox;
+use super::map::{Map, Tile};
+use super::outcome;
+use super::physics::{Physics, PhysicsConfig};
+use super::player::Character;
+use super::replay::{InputFrame, Replay};
+use super::velocity::Velocity;
+use super::move_character;
+
+/// Problems that are not parse errors, but still make a map unplayable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LintError {
+    MissingRival,
+    WinUnreachable,
+    RivalStartsFarAway { distance: f32 },
+    RivalRecordedOnOtherMap,
+    RivalRecordedWithOtherPhysics,
+    RivalReplayDoesNotWin,
+}
+
+impl fmt::Display for LintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LintError::MissingRival => write!(f, "there are rival positions, but no Rival tile"),
+            LintError::WinUnreachable => write!(f, "no Win tile can be reached from the Player tile"),
+            LintError::RivalStartsFarAway { distance } => write!(f, "rival positions start {:.1} tiles away from the Rival tile", distance),
+            LintError::RivalRecordedOnOtherMap => write!(f, "rival positions were recorded on another version of the map, record them again"),
+            LintError::RivalRecordedWithOtherPhysics => write!(f, "the rival replay was recorded with other physics, record it again"),
+            LintError::RivalReplayDoesNotWin => write!(f, "the rival replay does not reach a Win tile, record it again"),
+        }
+    }
+}
+
+/// How far from its tile the rival may be on its first recorded position, in tiles.
+const RIVAL_START_TOLERANCE: f32 = 3.0;
+
+/// Simulates the player with the physics of `level` on `difficulty`,
+/// and a rival replay with those of the difficulty it was recorded on, like the game does.
+pub fn lint(map: &Map, physics_config: &PhysicsConfig, level: &str, difficulty: Difficulty) -> Vec<LintError> {
+    let mut errors = Vec::new();
+    let physics = physics_config.physics(Some(level), difficulty);
+    let rival_difficulty = map.rival.info().difficulty.unwrap_or(difficulty);
+    let rival_physics = physics_config.physics(Some(level), rival_difficulty);
+    let tile_position = |tile| map.iter()
+        .find(|&(i, j)| map[i][j] == tile)
+        .map(|(i, j)| map.tile_position(i, j, 0.0));
+    if !map.rival.info().fits(map) {
+        errors.push(LintError::RivalRecordedOnOtherMap);
+    } else if !map.rival.fits(map, &rival_physics) {
+        errors.push(LintError::RivalRecordedWithOtherPhysics);
+    } else if let Ghost::Replay(replay) = &map.rival {
+        if !replay.inputs.is_empty() && !replay_wins(map, physics_config, level, rival_difficulty, replay) {
+            errors.push(LintError::RivalReplayDoesNotWin);
+        }
+    }
+    match (tile_position(Tile::Rival), map.rival.start()) {
+        (None, Some(_)) => errors.push(LintError::MissingRival),
+        (Some(rival), Some(first)) => {
+            let distance = rival.truncate().distance(first) / Tile::SIZE;
+            if distance > RIVAL_START_TOLERANCE {
+                errors.push(LintError::RivalStartsFarAway { distance });
+            }
+        },
+        (_, None) => {},
+    }
+    if let Some(player) = tile_position(Tile::Player) {
+        if !Reachability::new(map, &physics).win_reachable(player) {
+            errors.push(LintError::WinUnreachable);
+        }
+    }
+    errors
+}
+
+/// Plays a replay back as the player, through the game's own systems, so it dies, respawns and reaches
+/// checkpoints as it did when it was recorded. Tells if it wins before its inputs run out.
+fn replay_wins(map: &Map, physics_config: &PhysicsConfig, level: &str, difficulty: Difficulty, replay: &Replay) -> bool {
+    let mut map = map.clone();
+    map.rival = Ghost::default();
+    let options = Options { level: level.to_string(), difficulty, ..Options::default() };
+    let mut harness = Harness::with_physics_config(map, options, physics_config.clone());
+    let script = Vec::from_iter(replay.inputs.iter().map(|&input| (1, input)));
+    matches!(harness.play(&script), Some(outcome::Outcome::Won(_)))
+}
+
+/// A sequence of inputs tried from every place the player can stand on.
+#[derive(Clone, Copy)]
+struct Program {
+    direction: Direction,
+    /// Frames the direction is held for.
+    hold: u32,
+    jumps: [Option<u32>; 2],
+}
+
+impl Program {
+    const MAX_FRAMES: u32 = 240;
+    fn all() -> Vec<Program> {
+        let mut programs = Vec::new();
+        for direction in [Direction::Left, Direction::Right] {
+            for hold in [12, Self::MAX_FRAMES] {
+                programs.push(Program { direction, hold, jumps: [None, None] });
+                for first in [0, 10] {
+                    programs.push(Program { direction, hold, jumps: [Some(first), None] });
+                    for delay in (4..=32).step_by(4) {
+                        programs.push(Program { direction, hold, jumps: [Some(first), Some(first + delay)] });
+                    }
+                }
+            }
+        }
+        programs
+    }
+    fn jumps_at(&self, frame: u32) -> bool {
+        self.jumps.contains(&Some(frame))
+    }
+}
+
+/// Finds out if the win tile can be reached by simulating the player with the game's own physics,
+/// one tick at a time, from every tile the player can land on.
+/// Enemies are ignored, and the player always starts standing still, so this is only an estimate.
+struct Reachability<'a> {
+    map: &'a Map,
+    ground: GroundGrid,
+    win: Vec<(Vec3, Hitbox)>,
+    player_hitbox: Hitbox,
+    physics: &'a Physics,
+}
+
+enum Outcome {
+    Won,
+    Landed(Vec3),
+    Lost,
+}
+
+impl<'a> Reachability<'a> {
+    fn new(map: &'a Map, physics: &'a Physics) -> Self {
+        let mut win = Vec::new();
+        let mut player_hitbox = Hitbox::default();
+        for (i, j) in map.iter() {
+            if let Some(tile_info) = map.get_tile_info(i, j) {
+                match (tile_info.tile_type, tile_info.hitbox) {
+                    (Tile::Win, Some(hitbox)) => win.push((tile_info.position, hitbox)),
+                    (Tile::Player, Some(hitbox)) => player_hitbox = hitbox,
+                    _ => {},
+                }
+            }
+        }
+        Reachability { map, ground: GroundGrid::new(map), win, player_hitbox, physics }
+    }
+
+    fn cell(&self, position: Vec3) -> (i64, i64) {
+        self.ground.cell(position)
+    }
+
+    fn win_reachable(&self, start: Vec3) -> bool {
+        let start = match self.simulate(start, None) {
+            Outcome::Won => return true,
+            Outcome::Landed(position) => position,
+            Outcome::Lost => return false,
+        };
+        let programs = Program::all();
+        let mut visited = HashSet::from([self.cell(start)]);
+        let mut queue = VecDeque::from([start]);
+        while let Some(position) = queue.pop_front() {
+            for program in programs.iter() {
+                match self.simulate(position, Some(program)) {
+                    Outcome::Won => return true,
+                    Outcome::Landed(position) => {
+                        if visited.insert(self.cell(position)) {
+                            queue.push_back(position);
+                        }
+                    },
+                    Outcome::Lost => {},
+                }
+            }
+        }
+        false
+    }
+
+    /// Runs a program until the player stands on a different tile, wins or falls off the map.
+    /// Without a program, just lets the player fall to the ground.
+    fn simulate(&self, mut position: Vec3, program: Option<&Program>) -> Outcome {
+        let start = self.cell(position);
+        let bottom = self.map.fall_limit();
+        let mut character = Character::default();
+        let mut velocity = Velocity::default();
+        let mut airborne = false;
+        for frame in 0..Program::MAX_FRAMES {
+            let direction = program
+                .filter(|program| frame < program.hold)
+                .map(|program| program.direction);
+            let input = InputFrame {
+                left: direction == Some(Direction::Left),
+                right: direction == Some(Direction::Right),
+                jump: program.map_or(false, |program| program.jumps_at(frame)),
+                // jumps are never cut short, to find out how far the player can get
+                jump_held: true,
+            };
+            let grounded = self.tick(&input, &mut position, &mut velocity, &mut character);
+            if self.won(position) {
+                return Outcome::Won;
+            }
+            if position.y < bottom {
+                return Outcome::Lost;
+            }
+            if grounded && (airborne || self.cell(position) != start || program.is_none()) {
+                return Outcome::Landed(position);
+            }
+            airborne |= !grounded;
+        }
+        Outcome::Lost
+    }
+
+    /// Same as the game's tick: `input`, then `character_movement`.
+    fn tick(&self, input: &InputFrame, position: &mut Vec3, velocity: &mut Velocity, character: &mut Character) -> bool {
+        input.apply(self.physics, character, velocity, &mut Direction::default());
+        move_character(self.physics, &self.player_hitbox, &self.ground, position, velocity, character)
+    }
+
+    fn won(&self, position: Vec3) -> bool {
+        self.win.iter().any(|(win_position, win_hitbox)| self.player_hitbox.collide(&position, win_hitbox, win_position).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::harness::{Lives, Outcome};
+    use super::super::map::read_map;
+
+    /// Walking left falls off the map, walking right wins.
+    const MAP: &str = "\
+size: 4x3
+spawn_offset: 0,0
+---
+Empty Empty Empty
+Ground Player Empty
+Ground Rival Empty
+Ground Win Empty
+";
+
+    /// A run that falls off the map once, respawns, then walks to the win tile.
+    fn respawning_run(map: &Map, options: &Options) -> Replay {
+        let mut harness = Harness::new(map.clone(), options.clone());
+        let lives = harness.player_lives();
+        for _ in 0..300 {
+            if harness.player_lives() != lives {
+                break;
+            }
+            harness.tick(InputFrame { left: true, ..InputFrame::default() });
+        }
+        assert_eq!(harness.player_lives(), Lives(lives.0 - 1));
+        match harness.play(&[(300, InputFrame { right: true, ..InputFrame::default() })]) {
+            Some(Outcome::Won(Ghost::Replay(replay))) => replay.clone(),
+            _ => panic!("The run should win after respawning"),
+        }
+    }
+
+    #[test]
+    fn a_replay_that_respawns_on_the_way_wins() {
+        let mut map = read_map("lint", MAP, None).unwrap();
+        let options = Options { difficulty: Difficulty::Easy, ..Options::default() };
+        map.rival = Ghost::Replay(respawning_run(&map, &options));
+        assert_eq!(lint(&map, &PhysicsConfig::default(), &options.level, options.difficulty), vec![]);
+    }
+
+    #[test]
+    fn a_replay_that_stops_before_the_win_tile_does_not_win() {
+        let mut map = read_map("lint", MAP, None).unwrap();
+        let options = Options { difficulty: Difficulty::Easy, ..Options::default() };
+        let mut replay = respawning_run(&map, &options);
+        replay.inputs.truncate(replay.inputs.len() - 10);
+        map.rival = Ghost::Replay(replay);
+        assert_eq!(
+            lint(&map, &PhysicsConfig::default(), &options.level, options.difficulty),
+            vec![LintError::RivalReplayDoesNotWin],
+        );
+    }
+}
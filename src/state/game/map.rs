@@ -1,23 +1,24 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
 use std::str::FromStr;
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
 use bevy::prelude::{Vec2, Vec3};
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
 
+use super::enemies::Patrol;
+use super::fnv::Fnv1a;
 use super::hitbox::Hitbox;
-use super::positions::Positions;
+use super::parse_error::{MapParseError, MapParseErrorReason, MapParseErrors};
+use super::ghosts::{read_ghost, Ghost};
 
+use crate::background::BackgroundType;
 use crate::sprite::{SpriteType, SpriteTypeStates, SpriteVariant};
-use crate::Timer;
 
-const LEVEL_00: &str = include_str!("../../../assets/maps/00_map.txt");
-const LEVEL_01: &str = include_str!("../../../assets/maps/01_map.txt");
-const LEVEL_02: &str = include_str!("../../../assets/maps/02_map.txt");
-const LEVEL_03: &str = include_str!("../../../assets/maps/03_map.txt");
+use enum_iterator::IntoEnumIterator;
 
-const POSITIONS_00: &str = include_str!("../../../assets/positions/00_positions.txt");
-const POSITIONS_01: &str = include_str!("../../../assets/positions/01_positions.txt");
-const POSITIONS_02: &str = include_str!("../../../assets/positions/02_positions.txt");
-const POSITIONS_03: &str = include_str!("../../../assets/positions/03_positions.txt");
-
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, Debug, IntoEnumIterator, PartialEq)]
 pub enum Tile {
     Empty,
     Ground,
@@ -27,6 +28,11 @@ pub enum Tile {
     Blue,
     Jeremy,
     Blocky,
+    /// Where the player comes back after dying, once it touched it.
+    Checkpoint,
+    Coin,
+    /// Like a coin, but usually harder to get to.
+    Gem,
 }
 
 impl FromStr for Tile {
@@ -43,11 +49,20 @@ impl FromStr for Tile {
             "Blue" => Ok(Tile::Blue),
             "Jeremy" => Ok(Tile::Jeremy),
             "Blocky" => Ok(Tile::Blocky),
+            "Checkpoint" => Ok(Tile::Checkpoint),
+            "Coin" => Ok(Tile::Coin),
+            "Gem" => Ok(Tile::Gem),
             _ => Err(()),
         }
     }
 }
 
+impl fmt::Display for Tile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
 impl Tile {
     pub const SIZE: f32 = 18.0;
     pub fn connects_to(self, other: Tile) -> bool {
@@ -63,49 +78,130 @@ pub struct TileInfo {
     pub position: Vec3,
     pub image: SpriteVariant,
     pub hitbox: Option<Hitbox>,
+    /// Overrides the enemy's default patrol.
+    pub patrol: Option<Patrol>,
 }
 
 type Line = Vec<Tile>;
+pub type Patrols = HashMap<(usize, usize), Patrol>;
+
+/// The block at the top of a map file, before the `---` line.
+/// Every entry is optional, e.g.:
+/// ```text
+/// name: Level 1
+/// author: Chaz
+/// size: 100x20
+/// background: Blue
+/// music: game_theme.ogg
+/// par_time: 24.7
+/// spawn_offset: 20,10
+/// patrol: 12,3 20,40
+/// ---
+/// ```
+/// `patrol` entries can be repeated, they set the speed and amplitude of the enemy on a tile.
+#[derive(Clone, Debug)]
+pub struct MapHeader {
+    pub name: String,
+    pub author: String,
+    pub width: usize,
+    pub height: usize,
+    pub background: BackgroundType,
+    /// Replaces the game theme while playing this map.
+    pub music: Option<String>,
+    /// Seconds.
+    pub par_time: Option<f32>,
+    /// How many tiles the bottom left corner of the map is away from the center of the screen
+    /// when the level starts.
+    pub spawn_offset: Vec2,
+}
+
+impl MapHeader {
+    const SEPARATOR: &'static str = "---";
+    const DEFAULT_HORIZONTAL_OFFSET: f32 = 20.0;
+}
 
+/// Writes the entries that `read_header` understands, one per line.
+impl fmt::Display for MapHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.name.is_empty() {
+            writeln!(f, "name: {}", self.name)?;
+        }
+        if !self.author.is_empty() {
+            writeln!(f, "author: {}", self.author)?;
+        }
+        writeln!(f, "size: {}x{}", self.width, self.height)?;
+        writeln!(f, "background: {}", self.background)?;
+        if let Some(music) = &self.music {
+            writeln!(f, "music: {}", music)?;
+        }
+        if let Some(par_time) = self.par_time {
+            writeln!(f, "par_time: {}", par_time)?;
+        }
+        writeln!(f, "spawn_offset: {},{}", self.spawn_offset.x, self.spawn_offset.y)
+    }
+}
+
+#[derive(Clone, TypeUuid)]
+#[uuid = "6b0f8a52-3c1e-4d8e-9a57-2f4c1d0b7e91"]
 pub struct Map {
+    pub header: MapHeader,
     values: Vec<Line>,
-    pub rival_positions: Positions,
+    /// Enemy patrols that differ from the default, by tile.
+    pub patrols: Patrols,
+    /// The run the rival plays back.
+    pub rival: Ghost,
 }
 
 impl Map {
-    const WIDTH: usize = 100;
-    const HEIGHT: usize = 20;
+    pub fn width(&self) -> usize {
+        self.header.width
+    }
+    pub fn height(&self) -> usize {
+        self.header.height
+    }
     fn left(&self, i: usize, j: usize) -> Tile {
         if i > 0 { self.values[i-1][j] } else { Tile::Empty }
     }
     fn right(&self, i: usize, j: usize) -> Tile {
-        if i+1 < Self::WIDTH { self.values[i+1][j] } else { Tile::Empty }
+        if i+1 < self.width() { self.values[i+1][j] } else { Tile::Empty }
     }
     fn below(&self, i: usize, j: usize) -> Tile {
         if j > 0 { self.values[i][j-1] } else { Tile::Empty }
     }
     fn above(&self, i: usize, j: usize) -> Tile {
-        if j+1 < Self::HEIGHT { self.values[i][j+1] } else { Tile::Empty }
+        if j+1 < self.height() { self.values[i][j+1] } else { Tile::Empty }
     }
     fn below_left(&self, i: usize, j: usize) -> Tile {
         if i > 0 && j > 0 { self.values[i-1][j-1] } else { Tile::Empty }
     }
     fn below_right(&self, i: usize, j: usize) -> Tile {
-        if i+1 < Self::WIDTH && j > 0 { self.values[i+1][j-1] } else { Tile::Empty }
+        if i+1 < self.width() && j > 0 { self.values[i+1][j-1] } else { Tile::Empty }
     }
     fn above_left(&self, i: usize, j: usize) -> Tile {
-        if i > 0 && j+1 < Self::HEIGHT { self.values[i-1][j+1] } else { Tile::Empty }
+        if i > 0 && j+1 < self.height() { self.values[i-1][j+1] } else { Tile::Empty }
     }
     fn above_right(&self, i: usize, j: usize) -> Tile {
-        if i+1 < Self::WIDTH && j+1 < Self::HEIGHT { self.values[i+1][j+1] } else { Tile::Empty }
+        if i+1 < self.width() && j+1 < self.height() { self.values[i+1][j+1] } else { Tile::Empty }
+    }
+    /// Replaces a tile, forgetting the patrol of the enemy that was there.
+    pub fn set(&mut self, i: usize, j: usize, tile: Tile) {
+        if self.values[i][j] != tile {
+            self.patrols.remove(&(i, j));
+        }
+        self.values[i][j] = tile;
+    }
+    pub fn tile_position(&self, i: usize, j: usize, layer: f32) -> Vec3 {
+        let start_point = (-self.header.spawn_offset * Tile::SIZE).extend(layer);
+        start_point + Tile::SIZE * Vec3::new(i as f32, j as f32, 0.0)
+    }
+    /// Characters below this height have fallen off the map.
+    pub fn fall_limit(&self) -> f32 {
+        self.tile_position(0, 0, 0.0).y - 2.0 * Tile::SIZE
     }
     pub fn get_tile_info(&self, i: usize, j: usize) -> Option<TileInfo> {
         use crate::sprite::SPRITES;
 
-        let position = |layer| {
-            let start_point = Vec3::new(-20.0 * Tile::SIZE,-((Self::HEIGHT/2) as f32) * Tile::SIZE, layer);
-            start_point + Tile::SIZE * Vec3::new(i as f32, j as f32, 0.0)
-        };
+        let position = |layer| self.tile_position(i, j, layer);
 
         let tile = self[i][j];
         match tile {
@@ -157,6 +253,7 @@ impl Map {
                     position: position(0.5),
                     image: SpriteVariant::Sprite(SPRITES[&SpriteType::Ground][&image_key]),
                     hitbox,
+                    patrol: None,
                 })
             },
             Tile::Win => Some(TileInfo {
@@ -167,6 +264,7 @@ impl Map {
                     relative_position: Vec3::default(), //TODO: better values
                     size: Vec2::new(5.0, 5.0), //TODO: better values
                 }),
+                patrol: None,
             }),
             Tile::Player => {
                 Some(TileInfo {
@@ -177,6 +275,7 @@ impl Map {
                         relative_position: Vec3::default(), //TODO: better values
                         size: Vec2::new(Tile::SIZE, Tile::SIZE), //TODO: better values
                     }),
+                    patrol: None,
                 })
             },
             Tile::Rival => {
@@ -188,6 +287,7 @@ impl Map {
                         relative_position: Vec3::default(), //TODO: better values
                         size: Vec2::new(Tile::SIZE, Tile::SIZE), //TODO: better values
                     }),
+                    patrol: None,
                 })
             },
             Tile::Blue => {
@@ -199,6 +299,7 @@ impl Map {
                         relative_position: Vec3::default(), //TODO: better values
                         size: Vec2::new(Tile::SIZE, Tile::SIZE), //TODO: better values
                     }),
+                    patrol: None,
                 })
             },
             Tile::Jeremy => {
@@ -210,6 +311,7 @@ impl Map {
                         relative_position: Vec3::default(), //TODO: better values
                         size: Vec2::new(Tile::SIZE, Tile::SIZE), //TODO: better values
                     }),
+                    patrol: self.patrols.get(&(i, j)).copied(),
                 })
             },
             Tile::Blocky => {
@@ -221,15 +323,74 @@ impl Map {
                         relative_position: Vec3::default(), //TODO: better values
                         size: Vec2::new(Tile::SIZE, Tile::SIZE), //TODO: better values
                     }),
+                    patrol: self.patrols.get(&(i, j)).copied(),
                 })
             },
+            Tile::Checkpoint => {
+                Some(TileInfo {
+                    tile_type: tile,
+                    position: position(0.8),
+                    image: SpriteVariant::SpriteSheet(SpriteType::Flag),
+                    hitbox: Some(Hitbox {
+                        relative_position: Vec3::default(),
+                        size: Vec2::new(Tile::SIZE, Tile::SIZE),
+                    }),
+                    patrol: None,
+                })
+            },
+            Tile::Coin => {
+                Some(TileInfo {
+                    tile_type: tile,
+                    position: position(0.8),
+                    image: SpriteVariant::SpriteSheet(SpriteType::Coin),
+                    hitbox: Some(Hitbox {
+                        relative_position: Vec3::default(),
+                        size: Vec2::new(10.0, 10.0),
+                    }),
+                    patrol: None,
+                })
+            },
+            Tile::Gem => {
+                Some(TileInfo {
+                    tile_type: tile,
+                    position: position(0.8),
+                    image: SpriteVariant::Sprite(SPRITES[&SpriteType::Gem][&SpriteTypeStates::Full]),
+                    hitbox: Some(Hitbox {
+                        relative_position: Vec3::default(),
+                        size: Vec2::new(10.0, 10.0),
+                    }),
+                    patrol: None,
+                })
+            },
+        }
+    }
+    /// Changes with anything that moves things around in the level: the tiles, patrols and spawn offset.
+    /// It is the same on every platform and version of Rust, so it can be saved.
+    pub fn hash(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        hasher.write(&(self.width() as u64).to_le_bytes());
+        hasher.write(&(self.height() as u64).to_le_bytes());
+        hasher.write(&self.header.spawn_offset.x.to_le_bytes());
+        hasher.write(&self.header.spawn_offset.y.to_le_bytes());
+        for (i, j) in self.iter() {
+            hasher.write(&[self[i][j] as u8]);
+        }
+        let mut patrols = Vec::from_iter(self.patrols.iter());
+        patrols.sort_by_key(|(&tile, _)| tile);
+        for ((i, j), patrol) in patrols {
+            hasher.write(&(*i as u64).to_le_bytes());
+            hasher.write(&(*j as u64).to_le_bytes());
+            hasher.write(&patrol.speed.to_le_bytes());
+            hasher.write(&patrol.amplitude.to_le_bytes());
         }
+        hasher.finish()
     }
     pub fn tile_info_iter(&self) -> impl Iterator<Item = Option<TileInfo>> + '_ {
         self.iter().map(|(i, j)| self.get_tile_info(i, j))
     }
     pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
-        (0..Map::WIDTH).flat_map(|i| (0..Map::HEIGHT).map(move |j| (i, j)))
+        let height = self.height();
+        (0..self.width()).flat_map(move |i| (0..height).map(move |j| (i, j)))
     }
 }
 
@@ -240,6 +401,24 @@ impl std::ops::Deref for Map {
     }
 }
 
+/// Writes the map in the format read by `read_map_from_file`.
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.header)?;
+        let mut patrols = Vec::from_iter(self.patrols.iter());
+        patrols.sort_by_key(|(&tile, _)| tile);
+        for ((i, j), patrol) in patrols {
+            writeln!(f, "patrol: {},{} {},{}", i, j, patrol.speed, patrol.amplitude)?;
+        }
+        writeln!(f, "{}", MapHeader::SEPARATOR)?;
+        for column in self.values.iter() {
+            let column = Vec::from_iter(column.iter().map(Tile::to_string));
+            writeln!(f, "{}", column.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
 impl std::ops::Index<usize> for Map {
     type Output = Line;
     fn index(&self, i: usize) -> &Self::Output {
@@ -247,79 +426,236 @@ impl std::ops::Index<usize> for Map {
     }
 }
 
-pub const LEVEL_COUNT: usize = 4;
-
-fn convert_map_text_to_tiles(map_str_from_file: Vec<Vec<&str>>) -> Vec<Vec<Tile>> {
-    map_str_from_file.into_iter().map(|x|{
+/// `first_line` is the line number of the first column, for error messages.
+fn convert_map_text_to_tiles(file: &str, first_line: usize, map_str_from_file: Vec<Vec<&str>>, errors: &mut MapParseErrors) -> Vec<Vec<Tile>> {
+    map_str_from_file.into_iter().enumerate().map(|(i, x)|{
         x.into_iter().map(|v| {
-            Tile::from_str(v).unwrap()
+            Tile::from_str(v).unwrap_or_else(|_| {
+                errors.push(MapParseError::new(file, Some(first_line + i), Some(v), MapParseErrorReason::UnknownTile));
+                Tile::Empty
+            })
         }).collect()
     }).collect()
 }
 
-fn read_map_from_file(map: &str) -> Vec<Vec<Tile>>{
-    return convert_map_text_to_tiles(Vec::from_iter(map
-        .lines()
+fn read_patrol(value: &str) -> Option<((usize, usize), Patrol)> {
+    let (tile, patrol) = value.split_once(char::is_whitespace)?;
+    let (i, j) = tile.split_once(',')?;
+    let (speed, amplitude) = patrol.trim().split_once(',')?;
+    Some((
+        (usize::from_str(i.trim()).ok()?, usize::from_str(j.trim()).ok()?),
+        Patrol { speed: f32::from_str(speed.trim()).ok()?, amplitude: f32::from_str(amplitude.trim()).ok()? },
+    ))
+}
+
+fn read_header(
+    file: &str,
+    header: &[&str],
+    values: &[Line],
+    patrols: &mut Patrols,
+    errors: &mut MapParseErrors,
+) -> MapHeader {
+    let mut name = String::new();
+    let mut author = String::new();
+    let mut size = None;
+    let mut background = BackgroundType::default();
+    let mut music = None;
+    let mut par_time = None;
+    let mut spawn_offset = None;
+    for (i, line) in header.iter().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+        let mut error = |token: &str, reason| {
+            errors.push(MapParseError::new(file, Some(i + 1), Some(token), reason));
+        };
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => {
+                error(*line, MapParseErrorReason::InvalidHeaderEntry);
+                continue;
+            },
+        };
+        match key {
+            "name" => name = value.to_string(),
+            "author" => author = value.to_string(),
+            "size" => match value.split_once('x').map(|(w, h)| (usize::from_str(w.trim()), usize::from_str(h.trim()))) {
+                Some((Ok(width), Ok(height))) => size = Some((width, height)),
+                _ => error(value, MapParseErrorReason::InvalidValue),
+            },
+            "background" => match BackgroundType::from_str(value) {
+                Ok(value) => background = value,
+                Err(_) => error(value, MapParseErrorReason::InvalidValue),
+            },
+            "music" => music = Some(value.to_string()),
+            "par_time" => match f32::from_str(value) {
+                Ok(value) => par_time = Some(value),
+                Err(_) => error(value, MapParseErrorReason::InvalidValue),
+            },
+            "spawn_offset" => match value.split_once(',').map(|(x, y)| (f32::from_str(x.trim()), f32::from_str(y.trim()))) {
+                Some((Ok(x), Ok(y))) => spawn_offset = Some(Vec2::new(x, y)),
+                _ => error(value, MapParseErrorReason::InvalidValue),
+            },
+            "patrol" => match read_patrol(value) {
+                Some((tile, patrol)) => { patrols.insert(tile, patrol); },
+                None => error(value, MapParseErrorReason::InvalidValue),
+            },
+            _ => error(key, MapParseErrorReason::UnknownHeaderKey),
+        }
+    }
+    let (width, height) = size.unwrap_or_else(|| {
+        (values.len(), values.first().map(Vec::len).unwrap_or_default())
+    });
+    MapHeader {
+        name,
+        author,
+        width,
+        height,
+        background,
+        music,
+        par_time,
+        spawn_offset: spawn_offset.unwrap_or_else(|| {
+            Vec2::new(MapHeader::DEFAULT_HORIZONTAL_OFFSET, (height / 2) as f32)
+        }),
+    }
+}
+
+/// The index of the column of every `tile` in the map.
+fn columns_with(values: &[Line], tile: Tile) -> impl Iterator<Item = usize> + '_ {
+    values.iter().enumerate()
+        .flat_map(move |(i, column)| column.iter().filter(move |&&t| t == tile).map(move |_| i))
+}
+
+fn validate(file: &str, first_line: usize, header: &MapHeader, values: &[Line], errors: &mut MapParseErrors) {
+    if values.len() != header.width {
+        errors.push(MapParseError::new(file, None, None, MapParseErrorReason::WrongWidth {
+            expected: header.width,
+            found: values.len(),
+        }));
+    }
+    for (i, column) in values.iter().enumerate() {
+        if column.len() != header.height {
+            errors.push(MapParseError::new(file, Some(first_line + i), None, MapParseErrorReason::RaggedColumn {
+                expected: header.height,
+                found: column.len(),
+            }));
+        }
+    }
+    let players = Vec::from_iter(columns_with(values, Tile::Player));
+    match players.len() {
+        0 => errors.push(MapParseError::new(file, None, None, MapParseErrorReason::MissingPlayer)),
+        1 => {},
+        _ => for i in players {
+            errors.push(MapParseError::new(file, Some(first_line + i), Some("Player"), MapParseErrorReason::MultiplePlayers));
+        },
+    }
+    if columns_with(values, Tile::Win).next().is_none() {
+        errors.push(MapParseError::new(file, None, None, MapParseErrorReason::MissingWin));
+    }
+}
+
+/// A map file is an optional header, then one line per column of the map,
+/// from left to right, each listing its tiles from bottom to top.
+/// Tiles are separated by any whitespace, so columns may be indented or aligned.
+/// `file` is only used for error messages.
+pub fn read_map_from_file(file: &str, map: &str) -> Result<(MapHeader, Vec<Vec<Tile>>, Patrols), MapParseErrors> {
+    let mut errors = MapParseErrors::default();
+    let mut lines = Vec::from_iter(map.lines());
+    while lines.last().map_or(false, |line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    let (header, body, first_line) = match lines.iter().position(|line| line.trim() == MapHeader::SEPARATOR) {
+        Some(separator) => (&lines[..separator], &lines[separator + 1..], separator + 2),
+        None => (&lines[..0], &lines[..], 1),
+    };
+    let values = convert_map_text_to_tiles(file, first_line, Vec::from_iter(body
+        .iter()
         .map(|line| {
             line
-                .split(' ')
+                .split_whitespace()
                 .collect::<Vec<&str>>()
-        })));
+        })), &mut errors);
+    let mut patrols = Patrols::new();
+    let header = read_header(file, header, &values, &mut patrols, &mut errors);
+    validate(file, first_line, &header, &values, &mut errors);
+    errors.into_result((header, values, patrols))
 }
 
-fn read_positions_from_file(pos: &str) -> Vec<Vec3> {
-    return Vec::from_iter(pos
-        .lines()
-        .map(|s| {
-            let mut values = s.split(',').map(|v| {
-                f32::from_str(v).unwrap()
-            });
-            Vec3::new(values.next().unwrap(), values.next().unwrap(), values.next().unwrap())
-        }));
+/// Reads a map and, if given, its rival positions as `(file, text)`.
+pub fn read_map(file: &str, map: &str, positions: Option<(&str, &str)>) -> Result<Map, MapParseErrors> {
+    let (header, values, patrols) = read_map_from_file(file, map)?;
+    build_map(header, values, patrols, positions)
 }
 
+/// Makes a map out of tiles that come from somewhere other than a map file, e.g. another editor.
+/// `header` uses the same `key: value` entries as a map file, and the map is checked the same way.
+/// Errors found while converting the tiles can be passed in `errors`, to be reported together.
+pub fn import_map(
+    file: &str,
+    header: &[&str],
+    values: Vec<Vec<Tile>>,
+    mut patrols: Patrols,
+    positions: Option<(&str, &str)>,
+    mut errors: MapParseErrors,
+) -> Result<Map, MapParseErrors> {
+    let header = read_header(file, header, &values, &mut patrols, &mut errors);
+    validate(file, 1, &header, &values, &mut errors);
+    let header = errors.into_result(header)?;
+    build_map(header, values, patrols, positions)
+}
 
+fn build_map(
+    header: MapHeader,
+    values: Vec<Line>,
+    patrols: Patrols,
+    positions: Option<(&str, &str)>,
+) -> Result<Map, MapParseErrors> {
+    let rival = match positions {
+        Some((file, positions)) => read_ghost(file, positions)?,
+        None => Ghost::default(),
+    };
+    Ok(Map {
+        header,
+        values,
+        patrols,
+        rival,
+    })
+}
 
-pub fn read_map(i: usize) -> Map {
-    match i {
-        0 => Map {
-            values: read_map_from_file(LEVEL_00),
-            // To get rival_positions, play in training mode, then copy the output into the file:
-            // ../../../assets/positions/
-            rival_positions: Positions {
-                values: read_positions_from_file(POSITIONS_00).iter().copied().collect(), //TODO: is there a better way to do this?
-                timer: Timer::from_seconds(0.1, true),
-            }
-        },
-        1 => Map {
-            values: read_map_from_file(LEVEL_01),
-            /// To get rival_positions, play in training mode, then copy the output into the file:
-            // ../../../assets/positions/
-            rival_positions: Positions {
-                values: read_positions_from_file(POSITIONS_01).iter().copied().collect(), //TODO: is there a better way to do this?
-                timer: Timer::from_seconds(0.1, true),
-            }
-        },
-        2 => Map {
-            values: read_map_from_file(LEVEL_02),
-            // To get rival_positions, play in training mode, then copy the output into the file:
-            // ../../../assets/positions/
-            rival_positions: Positions {
-                values: read_positions_from_file(POSITIONS_02).iter().copied().collect(), //TODO: is there a better way to do this?
-                timer: Timer::from_seconds(0.1, true),
-            }
-        },
-        3 => Map {
-            values: read_map_from_file(LEVEL_03),
-            // To get rival_positions, play in training mode, then copy the output into the file:
-            // ../../../assets/positions/
-            rival_positions: Positions {
-                values: read_positions_from_file(POSITIONS_03).iter().copied().collect(), //TODO: is there a better way to do this?
-                timer: Timer::from_seconds(0.1, true),
-            }
-        },
-        LEVEL_COUNT.. => panic!("Tried to access a level greater than level count"),
-        _ => panic!(),
+/// Rival positions live next to the maps, in `positions/NN_positions.txt`.
+/// To get them, beat the level in training mode, and the `Recorder` writes that file.
+/// They are read together with the map, so editing only the positions file
+/// is picked up the next time the map itself is reloaded.
+pub fn positions_path(map_path: &Path) -> Option<String> {
+    let stem = map_path.file_stem()?.to_str()?;
+    let level = stem.strip_suffix("_map").unwrap_or(stem);
+    Some(format!("positions/{}_positions.txt", level))
+}
+
+#[derive(Default)]
+pub struct MapLoader;
+
+impl AssetLoader for MapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let file = load_context.path().display().to_string();
+            let positions = match positions_path(load_context.path()) {
+                Some(path) => match load_context.read_asset_bytes(&path).await {
+                    Ok(bytes) => Some((path, String::from_utf8(bytes)?)),
+                    Err(_) => None, // a level without a rival
+                },
+                None => None,
+            };
+            let positions = positions.as_ref().map(|(path, text)| (path.as_str(), text.as_str()));
+            let map = read_map(&file, std::str::from_utf8(bytes)?, positions)?;
+            load_context.set_default_asset(LoadedAsset::new(map));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["chazmap"]
     }
 }
 
@@ -327,8 +663,61 @@ pub fn read_map(i: usize) -> Map {
 pub enum RivalLevelPositions {
     #[default]
     HardCoded,
-    Stolen(Positions),
+    Stolen(Ghost),
 }
 
+/// Rival positions for each level, by level id.
 #[derive(Clone, Default)]
-pub struct RivalPositions(pub [RivalLevelPositions; LEVEL_COUNT]);
+pub struct RivalPositions(pub HashMap<String, RivalLevelPositions>);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_can_be_indented() {
+        let (_, values, _) = read_map_from_file("test", " Ground Player\n\tGround  Empty\n Ground Win \n").unwrap();
+        assert_eq!(values, vec![
+            vec![Tile::Ground, Tile::Player],
+            vec![Tile::Ground, Tile::Empty],
+            vec![Tile::Ground, Tile::Win],
+        ]);
+    }
+
+    #[test]
+    fn the_first_level_reads() {
+        // its columns start with a space
+        let text = include_str!("../../../assets/maps/00_map.chazmap");
+        assert!(read_map_from_file("00_map.chazmap", text).is_ok());
+    }
+
+    #[test]
+    fn an_unknown_tile_is_reported_with_its_line_and_token() {
+        let errors = read_map_from_file("test", "name: Test\n---\nGround Player\nGround Lava\nGround Win\n").unwrap_err();
+        assert_eq!(errors.0, vec![
+            MapParseError::new("test", Some(4), Some("Lava"), MapParseErrorReason::UnknownTile),
+        ]);
+    }
+
+    #[test]
+    fn every_error_is_reported_at_once() {
+        let errors = read_map_from_file("test", "size: 3x2\nmusic\n---\nGround Player\nGround\nGround Win\n").unwrap_err();
+        assert_eq!(errors.0, vec![
+            MapParseError::new("test", Some(2), Some("music"), MapParseErrorReason::InvalidHeaderEntry),
+            MapParseError::new("test", Some(5), None, MapParseErrorReason::RaggedColumn { expected: 2, found: 1 }),
+        ]);
+        assert_eq!(
+            errors.to_string(),
+            "test:2: header entries should look like `key: value` `music`\ntest:5: column has 1 tiles, expected 2",
+        );
+    }
+
+    #[test]
+    fn a_map_needs_a_player_and_a_win() {
+        let errors = read_map_from_file("test", "Ground Empty\n").unwrap_err();
+        assert_eq!(errors.0, vec![
+            MapParseError::new("test", None, None, MapParseErrorReason::MissingPlayer),
+            MapParseError::new("test", None, None, MapParseErrorReason::MissingWin),
+        ]);
+    }
+}
@@ -1,115 +1,289 @@
+use bevy::ecs::system::EntityCommands;
 use bevy::prelude::*;
 
 use crate::background::*;
 use crate::camera::*;
 use crate::controls::Controls;
 use crate::options::{Difficulty, Options};
-use crate::state::{AppState, GameOverEvent};
+use crate::state::AppState;
 use crate::sprite::*;
 
+mod checkpoint;
+use checkpoint::*;
+
 mod direction;
 
-mod enemies;
+mod fnv;
+
+pub mod enemies;
 use enemies::*;
 
+pub mod ghosts;
+use ghosts::*;
+
+mod grid;
+use grid::*;
+
+pub mod harness;
+
+mod health;
+use health::*;
+
 mod hitbox;
 use hitbox::*;
 
+pub mod levels;
+use levels::*;
+
+pub mod lint;
+
 pub mod map;
 use map::*;
 
+pub mod outcome;
+use outcome::*;
+
+pub mod parse_error;
+
+pub mod physics;
+use physics::*;
+
+pub mod tiled;
+use tiled::TiledLoader;
+
 mod player;
 use player::*;
 
 mod positions;
 use positions::*;
 
+pub mod recorder;
+use recorder::Recorder;
+
+mod replay;
+use replay::*;
+
+pub mod stats;
+use stats::*;
+
+mod tick;
+use tick::*;
+
 mod velocity;
 use velocity::*;
 
+/// Plays levels without drawing them: maps, characters, ticks and outcomes.
+/// It needs no window or renderer, so tests can run it on `MinimalPlugins`, see `harness`.
+pub struct GameSimulation;
+
+impl Plugin for GameSimulation {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<Map>()
+            .init_asset_loader::<MapLoader>()
+            .init_asset_loader::<TiledLoader>()
+            .add_asset::<LevelManifest>()
+            .init_asset_loader::<LevelManifestLoader>()
+            .add_asset::<PhysicsConfig>()
+            .init_asset_loader::<PhysicsConfigLoader>()
+            .init_resource::<Handle<PhysicsConfig>>()
+            .init_resource::<PhysicsConfig>()
+            .init_resource::<Physics>()
+            .init_resource::<Levels>()
+            .init_resource::<RivalPositions>()
+            .init_resource::<Recorder>()
+            .init_resource::<GameOutcome>()
+            .init_resource::<LevelStats>()
+            .init_resource::<BestStats>()
+            .init_resource::<GroundGrid>()
+            .init_resource::<ColliderGrid>()
+            .init_resource::<Events<CollisionEvent>>()
+            .insert_resource(GameTicks::new(SystemStage::single_threaded()
+                .with_system_set(SystemSet::new().label(TickStep::Input)
+                    // collision events last for the tick they happened in and the next one
+                    .with_system(Events::<CollisionEvent>::update_system)
+                    .with_system(play_replay)
+                    .with_system(record_replay)
+                    .with_system(update_invulnerability))
+                .with_system_set(SystemSet::new().label(TickStep::Velocity).after(TickStep::Input)
+                    .with_system(input))
+                .with_system_set(SystemSet::new().label(TickStep::Movement).after(TickStep::Velocity)
+                    .with_system(movement)
+                    .with_system(character_movement)
+                    .with_system(jeremy_movement)
+                    .with_system(blocky_movement)
+                    .with_system(update_rival_position))
+                .with_system_set(SystemSet::new().label(TickStep::Broadphase).after(TickStep::Movement)
+                    .with_system(update_collider_grid))
+                .with_system_set(SystemSet::new().label(TickStep::Collision).after(TickStep::Broadphase)
+                    .with_system(detect_collisions))
+                .with_system_set(SystemSet::new().label(TickStep::Checks).after(TickStep::Collision)
+                    .with_system(enemy_collision)
+                    .with_system(check_win)
+                    .with_system(rival_finished)
+                    .with_system(reach_checkpoint)
+                    .with_system(collect)
+                    .with_system(lose_life.after(enemy_collision).after(reach_checkpoint)))
+            ))
+            .add_system(update_physics_config)
+            .add_system_set(SystemSet::on_exit(AppState::Loading).with_system(load_stolen_positions))
+            .add_system_set(SystemSet::on_exit(AppState::Loading).with_system(load_best_stats))
+            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(load_level))
+            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(reset_ticks))
+            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(reset_outcome))
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(read_controls.label(FrameStep::Controls)))
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(run_ticks.exclusive_system().at_end().label(FrameStep::Ticks)))
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(resolve_outcome.exclusive_system().at_end().label(FrameStep::Outcome).after(FrameStep::Ticks)));
+    }
+}
+
+/// The simulation, and everything that shows it on screen.
 pub struct Game;
 
 impl Plugin for Game {
     fn build(&self, app: &mut App) {
         app
-            .init_resource::<RivalPositions>()
+            .add_plugin(GameSimulation)
             .add_system_set(SystemSet::on_enter(AppState::Game).with_system(reset_camera_position))
-            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(spawn_background))
-            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(load_level))
+            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(spawn_background.after(load_level)))
+            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(spawn_hearts))
+            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(spawn_lives_counter))
+            .add_system_set(SystemSet::on_enter(AppState::Game).with_system(spawn_collected_counter.after(load_level)))
             .add_system_set(SystemSet::on_update(AppState::Game).with_system(animation))
             .add_system_set(SystemSet::on_update(AppState::Game).with_system(update_direction))
             .add_system_set(SystemSet::on_update(AppState::Game).with_system(player_spritesheet))
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(input))
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(player_ground_collision))
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(player_enemy_collision))
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(check_win))
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(movement))
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(jeremy_movement))
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(blocky_movement))
             .add_system_set(SystemSet::on_update(AppState::Game).with_system(camera_movement))
             .add_system_set(SystemSet::on_update(AppState::Game).with_system(update_background))
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(out_of_bounds))
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(record_player_position))
-            .add_system_set(SystemSet::on_update(AppState::Game).with_system(update_rival_position));
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(blink))
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(update_hearts))
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(update_lives_counter))
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(update_collected_counter))
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(update_checkpoint_flags))
+            .add_system_set(SystemSet::on_update(AppState::Game).with_system(hide_stomped_enemies))
+            .add_system_set(SystemSet::on_exit(AppState::Game).with_system(reset_background))
+            .add_system_set(SystemSet::on_exit(AppState::Game).with_system(despawn_hearts))
+            .add_system_set(SystemSet::on_exit(AppState::Game).with_system(despawn_lives_counter))
+            .add_system_set(SystemSet::on_exit(AppState::Game).with_system(despawn_collected_counter));
     }
 }
 
 fn load_level(
+    maps: Res<Assets<Map>>,
+    levels: Res<Levels>,
     rival_positions: Res<RivalPositions>,
     options: Res<Options>,
+    physics_config: Res<PhysicsConfig>,
+    mut physics: ResMut<Physics>,
+    mut stats: ResMut<LevelStats>,
+    mut background: ResMut<CurrentBackground>,
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    sprite_handles: Res<SpriteHandles>,
-    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
-    mut textures: ResMut<Assets<Image>>,
+    // missing when playing headless, then spritesheets are left empty
+    sprite_handles: Option<Res<SpriteHandles>>,
+    mut texture_atlases: Option<ResMut<Assets<TextureAtlas>>>,
+    mut textures: Option<ResMut<Assets<Image>>>,
+    // missing when playing headless, where the level is always there
+    state: Option<ResMut<State<AppState>>>,
 ) {
     crate::console_log!("{:?}", options);
-    let mut spawn = |name| {
-        spawn(
+    let mut spawn = |name| match (&sprite_handles, &mut texture_atlases, &mut textures) {
+        (Some(sprite_handles), Some(texture_atlases), Some(textures)) => spawn(
             name,
-            &sprite_handles,
-            &mut texture_atlases,
-            &mut textures,
-        )
+            sprite_handles,
+            texture_atlases,
+            textures,
+        ),
+        _ => Handle::default(),
+    };
+    let map = match levels.map(&options.level).and_then(|handle| maps.get(handle)) {
+        Some(map) => map,
+        None => {
+            crate::console_log!("Level {} can not be played, its map is missing", options.level);
+            if let Some(mut state) = state {
+                state.set(AppState::Menu).unwrap();
+            }
+            return;
+        },
     };
-    let map = read_map(options.level);
-    let rival_positions = match &rival_positions.0[options.level] {
-        RivalLevelPositions::HardCoded => map.rival_positions.clone(), //TODO: unnecessary clone?
-        RivalLevelPositions::Stolen(positions) => positions.clone(),
+    background.0 = map.header.background;
+    *physics = physics_config.physics(Some(&options.level), options.difficulty);
+    commands.insert_resource(GroundGrid::new(map));
+    *stats = LevelStats::default();
+    let stolen_ghost = match rival_positions.0.get(&options.level) {
+        None | Some(RivalLevelPositions::HardCoded) => None,
+        Some(RivalLevelPositions::Stolen(ghost)) => Some(ghost),
     };
+    // a replaying rival keeps the physics of the difficulty it was recorded on, whatever the player's
+    let recorded_difficulty = |ghost: &Ghost| ghost.info().difficulty.unwrap_or(options.difficulty);
+    let recorded_physics = |ghost: &Ghost| physics_config.physics(Some(&options.level), recorded_difficulty(ghost));
+    // runs made before the map or the physics were changed are skipped, and a level without a rival run has an empty one
+    let rival_ghost = [stolen_ghost, Some(&map.rival)].into_iter()
+        .flatten()
+        .filter(|ghost| ghost.start().is_some())
+        .find(|ghost| ghost.fits(map, &recorded_physics(ghost)));
     for tile_info in map.tile_info_iter() {
         if let Some(tile_info) = tile_info {
-            let mut entity = commands.spawn();
-            match tile_info.image {
-                SpriteVariant::Sprite(path) => entity.insert_bundle(SpriteBundle {
-                        texture: asset_server.get_handle(path),
-                        transform: Transform::from_translation(tile_info.position),
-                        ..Default::default()
-                    }),
-                SpriteVariant::SpriteSheet(key) => entity.insert_bundle(SpriteSheetBundle {
-                        texture_atlas: spawn(key.to_string()),
-                        transform: Transform::from_translation(tile_info.position),
-                        ..Default::default()
-                    })
-                    .insert(SpriteTimer::from_seconds(0.2)),
-            };
+            let mut entity = spawn_tile_sprite(&mut commands, &tile_info, &asset_server, &mut spawn);
             if let Some(hitbox) = tile_info.hitbox {
                 match tile_info.tile_type {
                     Tile::Empty => panic!("Not possible to have a hitbox on an empty tile"),
-                    Tile::Ground => { entity.insert(GroundHitbox(hitbox)); },
-                    Tile::Win => { entity.insert( WinHitbox(hitbox) ); },
+                    Tile::Ground => {}, // in the `GroundGrid`
+                    Tile::Win => { entity.insert(Collider::new(hitbox, Layers::WIN, Layers::NONE)); },
                     Tile::Player => {
                         entity.insert_bundle(PlayerBundle {
-                            ground_hitbox: PlayerGroundHitbox(hitbox.clone()),
-                            enemy_hitbox: PlayerEnemyHitbox(hitbox),
+                            collider: Collider::new(
+                                hitbox,
+                                Layers::CHARACTER,
+                                Layers::GROUND | Layers::ENEMY | Layers::WIN | Layers::CHECKPOINT | Layers::COLLECTIBLE,
+                            ),
+                            health: Health::new(options.difficulty),
+                            lives: Lives::new(options.difficulty),
+                            spawn_point: SpawnPoint(tile_info.position),
+                            replay: Replay {
+                                start: tile_info.position.truncate(),
+                                ..Default::default()
+                            },
                             ..Default::default()
                         });
                     },
                     Tile::Rival => {
+                        let ghost = match rival_ghost {
+                            Some(ghost) => ghost.clone(), //TODO: unnecessary clone?
+                            None => {
+                                crate::console_log!("The rival of level {} has no run recorded on this version of the map", options.level);
+                                entity.despawn();
+                                continue;
+                            },
+                        };
                         entity.insert_bundle(RivalBundle {
-                            positions: rival_positions.clone(),
+                            character: Character::with_skin(ghost.info().skin.unwrap_or(Skin::Blue)),
                             ..Default::default()
                         });
+                        let rival_difficulty = recorded_difficulty(&ghost);
+                        let rival_physics = recorded_physics(&ghost);
+                        match ghost {
+                            Ghost::Positions(positions) => {
+                                entity
+                                    .insert(positions.starting_at(tile_info.position))
+                                    .insert(GhostPlayback::default());
+                            },
+                            // replays are simulated like the player, so the rival needs everything the player has to move
+                            Ghost::Replay(replay) => {
+                                entity
+                                    .insert(Transform::from_translation(replay.start.extend(tile_info.position.z)))
+                                    .insert(InputFrame::default())
+                                    .insert(Velocity::default())
+                                    // and collides like the player, so it stomps, gets hurt and respawns where the player did,
+                                    // with the health and lives of the difficulty it was recorded on
+                                    .insert(Collider::new(hitbox, Layers::CHARACTER, Layers::GROUND | Layers::ENEMY | Layers::CHECKPOINT))
+                                    .insert(Health::new(rival_difficulty))
+                                    .insert(Lives::new(rival_difficulty))
+                                    .insert(Stomped::default())
+                                    .insert(SpawnPoint(replay.start.extend(tile_info.position.z)))
+                                    .insert(rival_physics)
+                                    .insert(replay);
+                            },
+                        }
                         let mut spawn_torch = |scale| {
                             entity.with_children(|parent| {
                                 parent.spawn_bundle(SpriteBundle {
@@ -134,26 +308,61 @@ fn load_level(
                         };
                     },
                     Tile::Blue => {
-                        entity.insert(EnemyHitbox(hitbox));
+                        entity.insert(Collider::new(hitbox, Layers::ENEMY, Layers::NONE));
                     },
                     Tile::Jeremy => {
-                        entity.insert(EnemyHitbox(hitbox));
+                        entity.insert(Collider::new(hitbox, Layers::ENEMY, Layers::NONE));
                         entity.insert(InitialPosition(tile_info.position));
                         entity.insert(direction::Direction::Left);
+                        entity.insert(tile_info.patrol.unwrap_or(Patrol::JEREMY));
                         entity.insert(Jeremy);
                     },
                     Tile::Blocky => {
-                        entity.insert(EnemyHitbox(hitbox));
+                        entity.insert(Collider::new(hitbox, Layers::ENEMY, Layers::NONE));
                         entity.insert(InitialPosition(tile_info.position));
                         entity.insert(direction::Direction::Up);
+                        entity.insert(tile_info.patrol.unwrap_or(Patrol::BLOCKY));
                         entity.insert(Blocky);
                     },
+                    Tile::Checkpoint => {
+                        entity.insert(Collider::new(hitbox, Layers::CHECKPOINT, Layers::NONE));
+                        entity.insert(Checkpoint::default());
+                    },
+                    Tile::Coin | Tile::Gem => {
+                        entity.insert(Collider::new(hitbox, Layers::COLLECTIBLE, Layers::NONE));
+                        entity.insert(Collectible);
+                        stats.total += 1;
+                    },
                 }
             }
         }
     }
 }
 
+/// Spawns the sprite of a tile, without any of the components it needs to play.
+pub fn spawn_tile_sprite<'w, 's, 'a>(
+    commands: &'a mut Commands<'w, 's>,
+    tile_info: &TileInfo,
+    asset_server: &AssetServer,
+    mut spawn: impl FnMut(String) -> Handle<TextureAtlas>,
+) -> EntityCommands<'w, 's, 'a> {
+    let mut entity = commands.spawn();
+    match tile_info.image {
+        SpriteVariant::Sprite(path) => entity.insert_bundle(SpriteBundle {
+                texture: asset_server.get_handle(path),
+                transform: Transform::from_translation(tile_info.position),
+                ..Default::default()
+            }),
+        SpriteVariant::SpriteSheet(key) => entity.insert_bundle(SpriteSheetBundle {
+                texture_atlas: spawn(key.to_string()),
+                transform: Transform::from_translation(tile_info.position),
+                ..Default::default()
+            })
+            .insert(SpriteTimer::from_seconds(0.2)),
+    };
+    entity
+}
+
 fn animation(
     time: Res<Time>,
     texture_atlases: Res<Assets<TextureAtlas>>,
@@ -188,46 +397,143 @@ fn player_spritesheet(
     }
 }
 
-fn input(
+/// Runs every frame, a jump is kept until a tick uses it.
+fn read_controls(
     input: Res<Input<KeyCode>>,
-    mut query: Query<(&mut Character, &Controls, &mut Velocity, &mut direction::Direction)>,
+    mut query: Query<(&Controls, &mut InputFrame)>,
 ) {
-    for (mut player, controls, mut velocity, mut direction) in query.iter_mut() {
-        let new_direction = direction::Direction::from_input(input.pressed(controls.left), input.pressed(controls.right));
-        velocity.update(new_direction);
-        if let Some(new_direction) = new_direction {
-            *direction = new_direction;
-        }
-        player.update_walk_state(velocity.x);
+    for (controls, mut frame) in query.iter_mut() {
+        frame.left = input.pressed(controls.left);
+        frame.right = input.pressed(controls.right);
+        frame.jump |= input.just_pressed(controls.jump);
+        frame.jump_held = input.pressed(controls.jump);
+    }
+}
 
-        if input.just_pressed(controls.jump) {
-            if let Ok(_) = player.try_jump() {
-                velocity.y = 500.0;
-            }
-        }
+fn play_replay(mut query: Query<(&mut InputFrame, &mut Replay), With<Rival>>) {
+    for (mut frame, mut replay) in query.iter_mut() {
+        *frame = replay.next().unwrap_or_default();
     }
 }
 
-fn movement(
-    time: Res<Time>,
-    mut query: Query<(&mut Velocity, &mut Transform)>,
+fn record_replay(mut query: Query<(&InputFrame, &mut Replay), With<Player>>) {
+    for (frame, mut replay) in query.iter_mut() {
+        replay.inputs.push(*frame);
+    }
+}
+
+/// Characters with `Physics` of their own, rivals replaying a run, move with them instead of the level's.
+fn input(
+    physics: Res<Physics>,
+    mut query: Query<(&mut Character, &mut InputFrame, &mut Velocity, &mut direction::Direction, Option<&Physics>)>,
 ) {
+    for (mut character, mut frame, mut velocity, mut direction, own_physics) in query.iter_mut() {
+        frame.apply(own_physics.unwrap_or(&*physics), &mut character, &mut velocity, &mut direction);
+        frame.jump = false;
+    }
+}
+
+fn movement(physics: Res<Physics>, mut query: Query<(&mut Velocity, &mut Transform), Without<Character>>) {
     for (mut velocity, mut transform) in query.iter_mut() {
-        velocity.apply_gravity(time.delta_seconds());
-        transform.translation += velocity.0 * time.delta_seconds();
+        velocity.apply_gravity(&physics, TICK_SECONDS);
+        transform.translation += velocity.0 * TICK_SECONDS;
+    }
+}
+
+/// Characters stop where they touch the ground, instead of being pushed out of it afterwards.
+fn character_movement(
+    physics: Res<Physics>,
+    ground_grid: Res<GroundGrid>,
+    mut character_query: Query<(&mut Character, &Collider, &mut Transform, &mut Velocity, Option<&Physics>)>,
+) {
+    for (mut character, collider, mut transform, mut velocity, own_physics) in character_query.iter_mut() {
+        let physics = own_physics.unwrap_or(&*physics);
+        if collider.mask.contains(Layers::GROUND) {
+            move_character(physics, &collider.hitbox, &ground_grid, &mut transform.translation, &mut velocity, &mut character);
+        } else {
+            velocity.apply_gravity(physics, TICK_SECONDS);
+            character.slide(physics, &mut velocity);
+            transform.translation += velocity.0 * TICK_SECONDS;
+        }
     }
 }
 
+/// A tick of a character's movement against the ground, after its inputs were applied.
+/// `lint` simulates the player with it too, so both move characters the same way.
+/// Returns whether the character landed on the ground.
+fn move_character(
+    physics: &Physics,
+    hitbox: &Hitbox,
+    ground_grid: &GroundGrid,
+    position: &mut Vec3,
+    velocity: &mut Velocity,
+    character: &mut Character,
+) -> bool {
+    velocity.apply_gravity(physics, TICK_SECONDS);
+    character.slide(physics, velocity);
+    // a character's own tile is up to half a tile from its center, it touches ground a tile from its center,
+    // and it can also move that far this tick, however fast the physics let it go
+    let reach = velocity.0.truncate().abs().max_element() * TICK_SECONDS;
+    let radius = (1.5 + reach / Tile::SIZE) as i64;
+    let start = *position;
+    sweep_move(physics, hitbox, position, velocity, character, || ground_grid.near(start, radius))
+}
+
+/// Moves a character by its velocity for a tick, first horizontally then vertically,
+/// stopping each move at the first of `grounds` it touches, and tells the character what it touched.
+/// Returns whether the character landed on the ground.
+fn sweep_move<'a, I: Iterator<Item = (&'a Hitbox, Vec3)>>(
+    physics: &Physics,
+    hitbox: &Hitbox,
+    position: &mut Vec3,
+    velocity: &mut Velocity,
+    character: &mut Character,
+    grounds: impl Fn() -> I,
+) -> bool {
+    let mut landed = false;
+    let mut wall = None;
+    for axis in [Axis::X, Axis::Y] {
+        let distance = velocity[axis.index()] * TICK_SECONDS;
+        let contact = grounds()
+            .filter_map(|(ground, ground_position)| hitbox.sweep(position, axis, distance, ground, &ground_position))
+            .min_by(|a, b| a.time.total_cmp(&b.time));
+        let contact = match contact {
+            Some(contact) => contact,
+            None => {
+                position[axis.index()] += distance;
+                continue;
+            },
+        };
+        position[axis.index()] = contact.position;
+        match axis {
+            Axis::X => {
+                velocity.stop_left();
+                velocity.stop_right();
+                wall = Some(if distance > 0.0 { direction::Direction::Right } else { direction::Direction::Left });
+            },
+            Axis::Y if distance < 0.0 => {
+                velocity.y = 0.0;
+                character.hit_ground();
+                landed = true;
+            },
+            Axis::Y => velocity.stop_top(),
+        }
+    }
+    match (landed, wall) {
+        (true, _) => {},
+        (false, Some(wall)) => character.touch_wall(physics, wall),
+        (false, None) => character.leave_ground(physics),
+    }
+    landed
+}
+
 fn jeremy_movement(
-    time: Res<Time>,
-    mut query: Query<(&InitialPosition, &mut Transform, &mut direction::Direction), With<Jeremy>>,
+    mut query: Query<(&InitialPosition, &Patrol, &mut Transform, &mut direction::Direction), With<Jeremy>>,
 ) {
-    let speed = 20.0;
-    let movement_amplitude = 20.0;
-    for (initial_position, mut transform, mut direction) in query.iter_mut() {
-        transform.translation.x += f32::from(*direction) * speed * time.delta_seconds();
+    for (initial_position, patrol, mut transform, mut direction) in query.iter_mut() {
+        transform.translation.x += f32::from(*direction) * patrol.speed * TICK_SECONDS;
         let amplitude = transform.translation.x - initial_position.x;
-        if amplitude.abs() >= movement_amplitude {
+        if amplitude.abs() >= patrol.amplitude {
             *direction = if amplitude > 0.0 {
                 direction::Direction::Left
             } else {
@@ -238,27 +544,25 @@ fn jeremy_movement(
 }
 
 fn blocky_movement(
-    time: Res<Time>,
     asset_server: Res<AssetServer>,
-    mut query: Query<(&InitialPosition, &mut Transform, &mut direction::Direction, &mut Handle<Image>), With<Blocky>>,
+    mut query: Query<(&InitialPosition, &Patrol, &mut Transform, &mut direction::Direction, &mut Handle<Image>), With<Blocky>>,
 ) {
-    let movement_amplitude = 96.0;
-    for (initial_position, mut transform, mut direction, mut image) in query.iter_mut() {
+    for (initial_position, patrol, mut transform, mut direction, mut image) in query.iter_mut() {
         let (image_path, speed) = match *direction {
             direction::Direction::Up => (
                 SPRITES[&SpriteType::Blocky][&SpriteTypeStates::Surprised],
-                48.0
+                patrol.speed
             ),
             direction::Direction::Down => (
                 SPRITES[&SpriteType::Blocky][&SpriteTypeStates::Pissed],
-                120.0
+                Blocky::FALL_SPEED
             ),
             _ => panic!("Blocky should only move up and down!"),
         };
         *image = asset_server.get_handle(image_path);
-        transform.translation.y += f32::from(*direction) * speed * time.delta_seconds();
+        transform.translation.y += f32::from(*direction) * speed * TICK_SECONDS;
         let amplitude = transform.translation.y - initial_position.y;
-        if amplitude >= movement_amplitude {
+        if amplitude >= patrol.amplitude {
             *direction = direction::Direction::Down
         }
         if amplitude <= 0.0 {
@@ -290,168 +594,126 @@ fn camera_movement(
     }
 }
 
-fn player_ground_collision(
-    ground_query: Query<(&GroundHitbox, &Transform), Without<PlayerGroundHitbox>>,
-    mut player_query: Query<(&mut Character, &PlayerGroundHitbox, &mut Transform, &mut Velocity), Without<GroundHitbox>>,
+/// Stomping an enemy bounces the character off it, any other touch hurts and knocks it back.
+/// Deaths are left to `lose_life`, so the player and a replaying rival die the same way.
+fn enemy_collision(
+    physics: Res<Physics>,
+    mut stats: ResMut<LevelStats>,
+    mut collisions: EventReader<CollisionEvent>,
+    enemy_query: Query<(&Collider, &Transform)>,
+    mut character_query: Query<(&Transform, &mut Velocity, &mut Health, &mut Stomped, Option<&Physics>, Option<&Player>)>,
 ) {
-    for (mut player, player_hitbox, mut player_transform, mut player_velocity) in player_query.iter_mut() {
-        for (ground_hitbox, ground_transform) in ground_query.iter() {
-            if let Some(collision) = player_hitbox.0.collide(&player_transform.translation, &ground_hitbox.0, &ground_transform.translation) {
-                match collision.collision_type {
-                    CollisionType::Bottom => {
-                        player_transform.translation.y += collision.overlap;
-                        if player_velocity.y < 0.0 {
-                            player_velocity.y = 0.0;
-                            player.hit_ground();
-                        }
-                    },
-                    CollisionType::Top => {
-                        player_transform.translation.y -= collision.overlap;
-                        player_velocity.stop_top();
-                    },
-                    CollisionType::Left => {
-                        player_transform.translation.x += collision.overlap;
-                        player_velocity.stop_left();
-                    },
-                    CollisionType::Right => {
-                        player_transform.translation.x -= collision.overlap;
-                        player_velocity.stop_right();
-                    },
-                };
-            }
+    for collision in collisions.iter() {
+        let enemy_position = match enemy_query.get(collision.b) {
+            Ok((enemy, transform)) if enemy.layer == Layers::ENEMY => transform.translation,
+            _ => continue,
+        };
+        let (transform, mut velocity, mut health, mut stomped, own_physics, player) = match character_query.get_mut(collision.a) {
+            Ok(character) => character,
+            _ => continue,
+        };
+        if stomped.0.contains(&collision.b) {
+            continue;
         }
-    }
-}
-
-fn player_enemy_collision(
-    mut game_over: EventWriter<GameOverEvent>,
-    mut state: ResMut<State<AppState>>,
-    mut commands: Commands,
-    enemy_query: Query<(Entity, &EnemyHitbox, &Transform), Without<PlayerGroundHitbox>>,
-    mut player_query: Query<(&PlayerEnemyHitbox, &Transform, &mut Velocity), (With<Character>, Without<GroundHitbox>)>,
-    options: Res<Options>,
-) {
-    for (player_hitbox, player_transform, mut player_velocity) in player_query.iter_mut() {
-        for (enemy_id, enemy_hitbox, enemy_transform) in enemy_query.iter() {
-            if let Some(collision) = player_hitbox.0.collide(&player_transform.translation, &enemy_hitbox.0, &enemy_transform.translation) {
-                match collision.collision_type {
-                    CollisionType::Bottom => {
-                        //TODO: change player and enemy states so that some animation plays or there is a chance to jump again or something
-                        commands.entity(enemy_id).despawn();
-                        player_velocity.y *= -1.0;
-                    },
-                    _ => {
-                        game_over.send(GameOverEvent {
-                            secondary_message: Some(format!("{} was killed by an enemy", options.name)),
-                            ..Default::default()
-                        });
-                        state.set(AppState::GameOver).unwrap_or(());
-                    },
+        let physics = own_physics.unwrap_or(&*physics);
+        match collision.side {
+            CollisionType::Bottom => {
+                //TODO: change player and enemy states so that some animation plays or there is a chance to jump again or something
+                stomped.0.insert(collision.b);
+                velocity.y = velocity.y.abs();
+                if player.is_some() {
+                    stats.stomped += 1;
+                }
+            },
+            _ => {
+                if !health.hurt() || health.is_dead() {
+                    continue;
+                }
+                let knockback = if transform.translation.x < enemy_position.x {
+                    direction::Direction::Left
+                } else {
+                    direction::Direction::Right
                 };
-            }
-        }
+                velocity.x = f32::from(knockback) * physics.knockback_speed;
+                velocity.y = physics.knockback_lift;
+            },
+        };
     }
 }
 
 fn check_win(
-    mut rival_positions: ResMut<RivalPositions>,
+    mut outcome: ResMut<GameOutcome>,
+    mut stats: ResMut<LevelStats>,
+    physics: Res<Physics>,
+    levels: Res<Levels>,
+    maps: Res<Assets<Map>>,
     options: Res<Options>,
-    mut game_over: EventWriter<GameOverEvent>,
-    mut state: ResMut<State<AppState>>,
-    player_query: Query<(&PlayerGroundHitbox, &Transform, &Positions), With<Player>>,
-    win_tile_query: Query<(&WinHitbox, &Transform), Without<Player>>,
+    mut collisions: EventReader<CollisionEvent>,
+    win_query: Query<&Collider>,
+    player_query: Query<(&Character, &Replay), With<Player>>,
 ) {
-    for (player_hitbox, player_transform, player_positions) in player_query.iter() {
-        for (win_hitbox, win_transform) in win_tile_query.iter() {
-            if let Some(_) = player_hitbox.0.collide(&player_transform.translation, &win_hitbox.0, &win_transform.translation) {
-                rival_positions.0[options.level] = RivalLevelPositions::Stolen(Positions {
-                    values: player_positions.values.iter().map(|p| *p - Vec3::new(0.0, 0.0, 1.0)).collect(),
-                    ..Default::default()
-                });
-                if options.difficulty == Difficulty::Training {
-                    //TODO: a more sophisticated way to do this
-                    use crate::log::*;
-                    console_log!("// To get rival_positions, play in training mode, then copy the output into the source code");
-                    console_log!("rival_positions: Positions {{");
-                    console_log!("values: vec![");
-                    for position in player_positions.values.iter() {
-                        console_log!("Vec3::new({}, {}, 1.5),", position.x, position.y)
-                    }
-                    console_log!("].iter().copied().collect(), //TODO: is there a better way to do this?");
-                    console_log!("timer: Timer::from_seconds({}, true),", player_positions.timer.duration().as_secs_f32());
-                    console_log!("}}");
-                }
-                game_over.send(GameOverEvent {
-                    main_message: "You\nwin".to_string(),
-                    ..Default::default()
-                });
-                state.set(AppState::GameOver).unwrap();
-            }
-        }
+    for collision in collisions.iter() {
+        let is_win = win_query.get(collision.b).map_or(false, |win| win.layer == Layers::WIN);
+        let (player, player_replay) = match player_query.get(collision.a) {
+            Ok(player) if is_win => player,
+            _ => continue,
+        };
+        let run = Ghost::Replay(Replay {
+            start: player_replay.start,
+            inputs: player_replay.inputs.clone(),
+            info: GhostInfo {
+                level: options.level.clone(),
+                map_hash: levels.map(&options.level).and_then(|handle| maps.get(handle)).map(Map::hash),
+                physics_hash: Some(physics.hash()),
+                difficulty: Some(options.difficulty),
+                player: options.name.clone(),
+                time: Some(player_replay.duration()),
+                skin: Some(player.skin),
+            },
+            played: 0,
+        });
+        stats.time = player_replay.duration();
+        outcome.decide(Outcome::Won(run));
     }
 }
 
-fn out_of_bounds(
-    mut game_over: EventWriter<GameOverEvent>,
-    mut state: ResMut<State<AppState>>,
-    windows: Res<Windows>,
-    player_query: Query<&Transform, With<Character>>,
-    camera_query: Query<&Transform, (With<MainCamera>, Without<Character>)>,
-    options: Res<Options>,
+/// Only the player picks things up, a rival replaying a run does not take them away from it.
+fn collect(
+    mut stats: ResMut<LevelStats>,
+    mut commands: Commands,
+    mut collisions: EventReader<CollisionEvent>,
+    collectible_query: Query<Entity, With<Collectible>>,
+    player_query: Query<Entity, With<Player>>,
 ) {
-    let camera_position = camera_query.single().translation;
-
-    let window = windows.get_primary().unwrap();
-    let screen_bottom = camera_position.y - window.height() / 2.0;
-
-    for transform in player_query.iter() {
-        if transform.translation.y < screen_bottom {
-            game_over.send(GameOverEvent {
-                secondary_message: Some(format!("{} fell from a great height", options.name)),
-                ..Default::default()
-            });
-            state.set(AppState::GameOver).unwrap();
+    for collision in collisions.iter() {
+        if collectible_query.get(collision.b).is_ok() && player_query.get(collision.a).is_ok() {
+            commands.entity(collision.b).despawn();
+            stats.collected += 1;
         }
     }
 }
 
-fn record_player_position(
-    time: Res<Time>,
-    mut query: Query<(&Transform, &mut Positions), With<Player>>,
+fn update_rival_position(
+    mut query: Query<(&mut Transform, &mut Character, &mut direction::Direction, &Positions, &mut GhostPlayback), With<Rival>>,
 ) {
-    for (transform, mut positions) in query.iter_mut() {
-        positions.timer.tick(time.delta());
-        if positions.timer.finished() {
-            positions.values.push_back(transform.translation);
+    for (mut transform, mut character, mut direction, positions, mut playback) in query.iter_mut() {
+        playback.advance(TICK_SECONDS);
+        if let Some(sample) = playback.sample(positions) {
+            transform.translation = sample.position;
+            character.set_animation(sample.animation);
+            *direction = sample.facing;
         }
     }
 }
 
-fn update_rival_position(
-    mut game_over: EventWriter<GameOverEvent>,
-    mut state: ResMut<State<AppState>>,
-    time: Res<Time>,
-    mut query: Query<(&mut Transform, &mut Positions), With<Rival>>,
-    options: Res<Options>,
+fn rival_finished(
+    mut outcome: ResMut<GameOutcome>,
+    replay_query: Query<&Replay, With<Rival>>,
+    positions_query: Query<(&Positions, &GhostPlayback), With<Rival>>,
 ) {
-    for (mut transform, mut positions) in query.iter_mut() {
-        if positions.values.is_empty() {
-            game_over.send(GameOverEvent {
-                secondary_message: Some(format!("{}'s rival was faster", options.name)),
-                ..Default::default()
-            });
-            state.set(AppState::GameOver).unwrap();
-            return;
-        }
-        positions.timer.tick(time.delta());
-        transform.translation = if positions.timer.finished() {
-            positions.values.pop_front().unwrap()
-        } else {
-            let proportion = positions.timer.elapsed_secs() / positions.timer.duration().as_secs_f32();
-            // use crate::log::*;
-            // console_log!("elapsed = {}, duration = {}, proportion = {}", positions.timer.elapsed_secs(), positions.timer.duration().as_secs_f32(), proportion);
-            //TODO: bug! when timer duration is large, you can see the character is not transitioning smoothly
-            proportion*positions.values[0] + (1.0-proportion)*transform.translation
-        }
+    let replay_finished = replay_query.iter().any(Replay::finished);
+    let positions_finished = positions_query.iter().any(|(positions, playback)| playback.finished(positions));
+    if replay_finished || positions_finished {
+        outcome.decide(Outcome::RivalWasFaster);
     }
 }
@@ -0,0 +1,103 @@
+use bevy::prelude::*;
+
+use super::ghosts::{save_stolen_positions, Ghost};
+use super::levels::Levels;
+use super::map::{RivalLevelPositions, RivalPositions};
+use super::recorder::Recorder;
+use super::stats::{BestStats, LevelStats};
+
+use crate::options::{Difficulty, Options};
+use crate::state::{AppState, GameOverEvent};
+
+/// How a level ended.
+#[derive(Clone, Debug)]
+pub enum Outcome {
+    /// The player reached a win tile, with this run.
+    Won(Ghost),
+    KilledByEnemy,
+    Fell,
+    RivalWasFaster,
+}
+
+impl Outcome {
+    /// When several outcomes happen on the same tick, the lowest one is kept.
+    /// Ties go to the player: winning beats everything, and a rival finishing at the same time is too late.
+    fn priority(&self) -> u8 {
+        match self {
+            Outcome::Won(_) => 0,
+            Outcome::KilledByEnemy => 1,
+            Outcome::Fell => 2,
+            Outcome::RivalWasFaster => 3,
+        }
+    }
+    fn event(&self, options: &Options, stats: &LevelStats) -> GameOverEvent {
+        let secondary_message = |message: String| GameOverEvent {
+            secondary_message: Some(message),
+            ..Default::default()
+        };
+        match self {
+            Outcome::Won(_) => GameOverEvent {
+                main_message: "You\nwin".to_string(),
+                secondary_message: Some(stats.to_string()),
+            },
+            Outcome::KilledByEnemy => secondary_message(format!("{} was killed by an enemy", options.name)),
+            Outcome::Fell => secondary_message(format!("{} fell from a great height", options.name)),
+            Outcome::RivalWasFaster => secondary_message(format!("{}'s rival was faster", options.name)),
+        }
+    }
+}
+
+/// The outcome decided by the checks of the last tick, if any.
+/// Once there is one, no more ticks run, and `resolve_outcome` ends the level at the end of the frame.
+#[derive(Default)]
+pub struct GameOutcome(Option<Outcome>);
+
+impl GameOutcome {
+    pub fn decide(&mut self, outcome: Outcome) {
+        if self.0.as_ref().map_or(true, |current| outcome.priority() < current.priority()) {
+            self.0 = Some(outcome);
+        }
+    }
+    pub fn get(&self) -> Option<&Outcome> {
+        self.0.as_ref()
+    }
+    pub fn is_decided(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+pub fn reset_outcome(mut outcome: ResMut<GameOutcome>) {
+    outcome.0 = None;
+}
+
+/// The only place a level ends, so it ends once, with one `GameOverEvent`.
+pub fn resolve_outcome(
+    mut outcome: ResMut<GameOutcome>,
+    mut rival_positions: ResMut<RivalPositions>,
+    recorder: Res<Recorder>,
+    stats: Res<LevelStats>,
+    mut best_stats: ResMut<BestStats>,
+    levels: Res<Levels>,
+    options: Res<Options>,
+    mut game_over: EventWriter<GameOverEvent>,
+    mut state: ResMut<State<AppState>>,
+) {
+    let decided = match &outcome.0 {
+        Some(outcome) => outcome,
+        None => return,
+    };
+    if state.set(AppState::GameOver).is_err() {
+        return; // another transition is queued, e.g. the game was just paused, try again next frame
+    }
+    game_over.send(decided.event(&options, &stats));
+    if let Some(Outcome::Won(run)) = outcome.0.take() {
+        best_stats.record(&options.level, options.difficulty, *stats);
+        save_stolen_positions(&options.level, &run);
+        if options.difficulty == Difficulty::Training {
+            if let Some(level) = levels.get(&options.level) {
+                recorder.export(level, &run);
+            }
+        }
+        rival_positions.0.insert(options.level.clone(), RivalLevelPositions::Stolen(run));
+    }
+}
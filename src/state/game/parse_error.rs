@@ -0,0 +1,110 @@
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapParseErrorReason {
+    InvalidHeaderEntry,
+    UnknownHeaderKey,
+    InvalidValue,
+    UnknownTile,
+    RaggedColumn { expected: usize, found: usize },
+    WrongWidth { expected: usize, found: usize },
+    MissingPlayer,
+    MultiplePlayers,
+    MissingWin,
+    InvalidPosition,
+    InvalidInput,
+    UnsupportedVersion { found: u32, supported: u32 },
+    InvalidTiledMap,
+}
+
+impl fmt::Display for MapParseErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapParseErrorReason::InvalidHeaderEntry => write!(f, "header entries should look like `key: value`"),
+            MapParseErrorReason::UnknownHeaderKey => write!(f, "unknown header key"),
+            MapParseErrorReason::InvalidValue => write!(f, "invalid value"),
+            MapParseErrorReason::UnknownTile => write!(f, "unknown tile"),
+            MapParseErrorReason::RaggedColumn { expected, found } => write!(f, "column has {} tiles, expected {}", found, expected),
+            MapParseErrorReason::WrongWidth { expected, found } => write!(f, "map has {} columns, expected {}", found, expected),
+            MapParseErrorReason::MissingPlayer => write!(f, "there is no Player tile"),
+            MapParseErrorReason::MultiplePlayers => write!(f, "there is more than one Player tile"),
+            MapParseErrorReason::MissingWin => write!(f, "there is no Win tile"),
+            MapParseErrorReason::InvalidPosition => write!(f, "positions should look like `x,y,z` or `x,y,z,animation,facing`"),
+            MapParseErrorReason::InvalidInput => write!(f, "inputs should look like `count flags`, e.g. `12 RJH`"),
+            MapParseErrorReason::UnsupportedVersion { found, supported } if found > supported => write!(f, "version {} is newer than the supported version {}", found, supported),
+            MapParseErrorReason::UnsupportedVersion { found, supported } => write!(f, "version {} is older than the supported version {}", found, supported),
+            MapParseErrorReason::InvalidTiledMap => write!(f, "invalid Tiled map"),
+        }
+    }
+}
+
+/// Something wrong in a map or positions file.
+/// In the body of a map file each line is a column of the map, so `line` also tells which column.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MapParseError {
+    pub file: String,
+    pub line: Option<usize>,
+    pub token: Option<String>,
+    pub reason: MapParseErrorReason,
+}
+
+impl MapParseError {
+    pub fn new(file: &str, line: Option<usize>, token: Option<&str>, reason: MapParseErrorReason) -> Self {
+        MapParseError {
+            file: file.to_string(),
+            line,
+            token: token.map(str::to_string),
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for MapParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        write!(f, ": {}", self.reason)?;
+        if let Some(token) = &self.token {
+            write!(f, " `{}`", token)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MapParseError {}
+
+/// Every problem found in a file, so they can all be fixed at once.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MapParseErrors(pub Vec<MapParseError>);
+
+impl MapParseErrors {
+    pub fn push(&mut self, error: MapParseError) {
+        self.0.push(error);
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    pub fn into_result<T>(self, value: T) -> Result<T, MapParseErrors> {
+        if self.is_empty() {
+            Ok(value)
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for MapParseErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, error) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MapParseErrors {}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::utils::BoxedFuture;
+
+use crate::log::console_log;
+use crate::options::{Difficulty, Options};
+
+use super::fnv::Fnv1a;
+
+pub const PHYSICS_PATH: &str = "physics.physics.ron";
+
+/// How characters move. Speeds are in pixels per second, times in seconds.
+/// Replays only play back the same with the physics they were recorded with,
+/// so a rival replaying a run has its own, and the level's are a resource.
+#[derive(Clone, Component, Copy, Debug, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Physics {
+    pub gravity: f32,
+    /// The fastest a character falls or rises.
+    pub terminal_speed: f32,
+    /// How much faster a character runs every second its direction is held, up to `max_run_speed`.
+    pub run_acceleration: f32,
+    pub max_run_speed: f32,
+    /// How quickly a character slows down while no direction is held:
+    /// its running speed is multiplied by `e^-friction` every second,
+    /// and it stops once slower than what `run_acceleration` adds in a tick.
+    pub friction: f32,
+    pub jump_speed: f32,
+    /// Jumps in a row before landing, the first one from the ground.
+    pub max_jump_count: u8,
+    /// After walking off a ledge, the character can still jump as if standing on it for this long.
+    pub coyote_time: f32,
+    /// Pressing jump this long before landing makes the character jump as soon as it lands.
+    pub jump_buffer_time: f32,
+    /// What the vertical speed of a rising jump is multiplied by when jump is released.
+    pub jump_cut: f32,
+    /// The fastest a character sliding down a wall falls.
+    pub wall_slide_speed: f32,
+    /// How fast a wall jump pushes the character away from the wall.
+    pub wall_jump_kick: f32,
+    /// How fast an enemy hit pushes the character away from the enemy.
+    pub knockback_speed: f32,
+    /// How fast an enemy hit throws the character up.
+    pub knockback_lift: f32,
+}
+
+impl Default for Physics {
+    fn default() -> Self {
+        Physics {
+            gravity: 500.0,
+            terminal_speed: 300.0,
+            run_acceleration: 600.0,
+            max_run_speed: 250.0,
+            // 0.9 every 60th of a second
+            friction: 6.32,
+            jump_speed: 500.0,
+            max_jump_count: 2,
+            coyote_time: 0.1,
+            jump_buffer_time: 0.1,
+            jump_cut: 0.5,
+            wall_slide_speed: 60.0,
+            wall_jump_kick: 200.0,
+            knockback_speed: 150.0,
+            knockback_lift: 200.0,
+        }
+    }
+}
+
+impl Physics {
+    /// Changes with any of the values, and like `Map::hash` it can be saved,
+    /// so a replay can tell if it is played back with the physics it was recorded with.
+    pub fn hash(&self) -> u64 {
+        let mut hasher = Fnv1a::default();
+        let values = [
+            self.gravity,
+            self.terminal_speed,
+            self.run_acceleration,
+            self.max_run_speed,
+            self.friction,
+            self.jump_speed,
+            self.coyote_time,
+            self.jump_buffer_time,
+            self.jump_cut,
+            self.wall_slide_speed,
+            self.wall_jump_kick,
+            self.knockback_speed,
+            self.knockback_lift,
+        ];
+        for value in values {
+            hasher.write(&value.to_le_bytes());
+        }
+        hasher.write(&[self.max_jump_count]);
+        hasher.finish()
+    }
+}
+
+/// Some of the values of `Physics`, to change for one level or difficulty.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct PhysicsOverrides {
+    pub gravity: Option<f32>,
+    pub terminal_speed: Option<f32>,
+    pub run_acceleration: Option<f32>,
+    pub max_run_speed: Option<f32>,
+    pub friction: Option<f32>,
+    pub jump_speed: Option<f32>,
+    pub max_jump_count: Option<u8>,
+    pub coyote_time: Option<f32>,
+    pub jump_buffer_time: Option<f32>,
+    pub jump_cut: Option<f32>,
+    pub wall_slide_speed: Option<f32>,
+    pub wall_jump_kick: Option<f32>,
+    pub knockback_speed: Option<f32>,
+    pub knockback_lift: Option<f32>,
+}
+
+impl PhysicsOverrides {
+    fn apply(&self, physics: &mut Physics) {
+        let values = [
+            (self.gravity, &mut physics.gravity),
+            (self.terminal_speed, &mut physics.terminal_speed),
+            (self.run_acceleration, &mut physics.run_acceleration),
+            (self.max_run_speed, &mut physics.max_run_speed),
+            (self.friction, &mut physics.friction),
+            (self.jump_speed, &mut physics.jump_speed),
+            (self.coyote_time, &mut physics.coyote_time),
+            (self.jump_buffer_time, &mut physics.jump_buffer_time),
+            (self.jump_cut, &mut physics.jump_cut),
+            (self.wall_slide_speed, &mut physics.wall_slide_speed),
+            (self.wall_jump_kick, &mut physics.wall_jump_kick),
+            (self.knockback_speed, &mut physics.knockback_speed),
+            (self.knockback_lift, &mut physics.knockback_lift),
+        ];
+        for (value, field) in values {
+            if let Some(value) = value {
+                *field = value;
+            }
+        }
+        if let Some(max_jump_count) = self.max_jump_count {
+            physics.max_jump_count = max_jump_count;
+        }
+    }
+}
+
+/// The contents of `assets/physics.physics.ron`, e.g.
+/// ```text
+/// #![enable(implicit_some)]
+/// (
+///     base: (gravity: 500.0, jump_speed: 500.0),
+///     levels: { "3": (gravity: 400.0) },
+///     difficulties: { TRAINING: (coyote_time: 0.2) },
+/// )
+/// ```
+/// Values missing from `base` are the defaults of `Physics`.
+/// Without `implicit_some`, overridden values have to be written like `gravity: Some(400.0)`.
+/// A level's overrides apply on top of `base`, then the difficulty's on top of those.
+/// The game picks up changes to the file while it runs, except on the web.
+#[derive(Clone, Debug, Default, Deserialize, TypeUuid)]
+#[uuid = "6b1e4c2a-93d7-4f0e-a5c8-2d7f9b3e1a64"]
+pub struct PhysicsConfig {
+    #[serde(default)]
+    pub base: Physics,
+    #[serde(default)]
+    pub levels: HashMap<String, PhysicsOverrides>,
+    #[serde(default)]
+    pub difficulties: HashMap<Difficulty, PhysicsOverrides>,
+}
+
+impl PhysicsConfig {
+    /// The physics of `level`, or of any level if there is none.
+    pub fn physics(&self, level: Option<&str>, difficulty: Difficulty) -> Physics {
+        let mut physics = self.base;
+        if let Some(overrides) = level.and_then(|level| self.levels.get(level)) {
+            overrides.apply(&mut physics);
+        }
+        if let Some(overrides) = self.difficulties.get(&difficulty) {
+            overrides.apply(&mut physics);
+        }
+        physics
+    }
+}
+
+pub fn read_physics_config(text: &str) -> Result<PhysicsConfig, ron::Error> {
+    ron::de::from_str(text)
+}
+
+#[derive(Default)]
+pub struct PhysicsConfigLoader;
+
+impl AssetLoader for PhysicsConfigLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let config: PhysicsConfig = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(config));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["physics.ron"]
+    }
+}
+
+/// Keeps the `PhysicsConfig` resource, and the `Physics` of the level, up to date with the file.
+pub fn update_physics_config(
+    mut events: EventReader<AssetEvent<PhysicsConfig>>,
+    configs: Res<Assets<PhysicsConfig>>,
+    options: Res<Options>,
+    mut config: ResMut<PhysicsConfig>,
+    mut physics: ResMut<Physics>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+        if let Some(new_config) = configs.get(handle) {
+            if matches!(event, AssetEvent::Modified { .. }) {
+                console_log!("Reloaded the physics, replays recorded before may not play back the same");
+            }
+            *config = new_config.clone();
+            *physics = config.physics(Some(&options.level), options.difficulty);
+        }
+    }
+}
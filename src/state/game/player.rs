@@ -3,9 +3,14 @@ use bevy::prelude::*;
 use crate::controls::Controls;
 use crate::sprite::SpriteType;
 
+use super::checkpoint::{Lives, SpawnPoint};
 use super::direction::Direction;
-use super::hitbox::{PlayerGroundHitbox, PlayerEnemyHitbox};
-use super::positions::Positions;
+use super::enemies::Stomped;
+use super::health::Health;
+use super::hitbox::Collider;
+use super::replay::{InputFrame, Replay};
+use super::physics::Physics;
+use super::tick::TICK_SECONDS;
 use super::velocity::Velocity;
 
 #[derive(Component, Default)]
@@ -16,11 +21,17 @@ pub struct PlayerBundle {
     pub player: Player,
     pub character: Character,
     pub controls: Controls,
-    pub ground_hitbox: PlayerGroundHitbox,
-    pub enemy_hitbox: PlayerEnemyHitbox,
+    pub collider: Collider,
+    pub health: Health,
+    pub lives: Lives,
+    pub stomped: Stomped,
+    /// Starts where the player does.
+    pub spawn_point: SpawnPoint,
     pub velocity: Velocity,
     pub direction: Direction,
-    pub positions: Positions,
+    pub input_frame: InputFrame,
+    /// The run so far, to become the rival's if the player wins.
+    pub replay: Replay,
 }
 
 #[derive(Component, Default)]
@@ -31,7 +42,6 @@ pub struct RivalBundle {
     pub rival: Rival,
     pub character: Character,
     pub direction: Direction,
-    pub positions: Positions,
 }
 
 #[derive(Clone, Copy, Default, PartialEq)]
@@ -40,16 +50,105 @@ enum State {
     Idle,
     Walking,
     InTheAir(u8),
+    /// Falling along a wall on that side.
+    WallSliding(Direction),
+}
+
+/// What a character is doing, as far as its spritesheet is concerned.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Animation {
+    #[default]
+    Idle,
+    Walking,
+    Jumping,
+}
+
+impl std::str::FromStr for Animation {
+
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Animation, Self::Err> {
+        match input {
+            "Idle" => Ok(Animation::Idle),
+            "Walking" => Ok(Animation::Walking),
+            "Jumping" => Ok(Animation::Jumping),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for Animation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Skin {
+    #[default]
+    Green,
+    Blue,
+}
+
+impl std::str::FromStr for Skin {
+
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Skin, Self::Err> {
+        match input {
+            "Green" => Ok(Skin::Green),
+            "Blue" => Ok(Skin::Blue),
+            _ => Err(()),
+        }
+    }
+}
+
+impl std::fmt::Display for Skin {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self, f)
+    }
 }
 
 #[derive(Component, Default)]
 pub struct Character {
     state: State,
-    previous_state: State,
+    /// The state the spritesheet was last picked for, if any.
+    previous_state: Option<State>,
+    pub skin: Skin,
+    /// Ticks since the character walked off the ground, while it has not jumped.
+    ticks_off_ground: u32,
+    /// Ticks left for a jump pressed too early to happen.
+    buffered_jump: u32,
+    /// The character is going up from a jump, which can still be cut short.
+    rising: bool,
+}
+
+fn ticks(seconds: f32) -> u32 {
+    (seconds / TICK_SECONDS).round() as u32
 }
 
 impl Character {
-    const MAX_JUMP_COUNT: u8 = 2;
+    pub fn with_skin(skin: Skin) -> Self {
+        Character {
+            skin,
+            ..Default::default()
+        }
+    }
+    pub fn animation(&self) -> Animation {
+        match self.state {
+            State::Idle => Animation::Idle,
+            State::Walking => Animation::Walking,
+            State::InTheAir(_) | State::WallSliding(_) => Animation::Jumping,
+        }
+    }
+    /// For characters that replay a run instead of being controlled.
+    pub fn set_animation(&mut self, animation: Animation) {
+        self.state = match animation {
+            Animation::Idle => State::Idle,
+            Animation::Walking => State::Walking,
+            Animation::Jumping => State::InTheAir(1),
+        };
+    }
     fn get_jump_count(&self) -> u8 {
         if let State::InTheAir(jump_count) = self.state {
             jump_count
@@ -57,8 +156,11 @@ impl Character {
             0
         }
     }
+    fn airborne(&self) -> bool {
+        matches!(self.state, State::InTheAir(_) | State::WallSliding(_))
+    }
     pub fn update_walk_state(&mut self, x_velocity: f32) {
-        if let State::InTheAir(_) = self.state {
+        if self.airborne() {
             return;
         }
         self.state = if x_velocity != 0.0 {
@@ -67,29 +169,95 @@ impl Character {
                 State::Idle
             };
     }
-    pub fn try_jump(&mut self) -> Result<(), ()> {
+    fn try_jump(&mut self, physics: &Physics) -> Result<(), ()> {
         let jump_count = self.get_jump_count();
-        if jump_count < Self::MAX_JUMP_COUNT {
+        if jump_count < physics.max_jump_count {
             self.state = State::InTheAir(jump_count+1);
             Ok(())
         } else {
             Err(())
         }
     }
+    /// Jumps if jump was `pressed` now, or a bit before a jump was possible,
+    /// and cuts a rising jump short once jump is not `held` anymore.
+    pub fn update_jump(&mut self, physics: &Physics, pressed: bool, held: bool, velocity: &mut Velocity) {
+        if pressed {
+            self.buffered_jump = ticks(physics.jump_buffer_time).max(1);
+        }
+        if self.buffered_jump > 0 {
+            self.buffered_jump -= 1;
+            if let State::WallSliding(wall) = self.state {
+                // kicks away from the wall, with the double jump left
+                self.state = State::InTheAir(1);
+                self.buffered_jump = 0;
+                self.rising = true;
+                velocity.x = -f32::from(wall) * physics.wall_jump_kick;
+                velocity.y = physics.jump_speed;
+                return;
+            }
+            if let Ok(_) = self.try_jump(physics) {
+                self.buffered_jump = 0;
+                self.rising = true;
+                velocity.y = physics.jump_speed;
+                return;
+            }
+        }
+        if velocity.y <= 0.0 {
+            self.rising = false;
+        }
+        if self.rising && !held {
+            self.rising = false;
+            velocity.y *= physics.jump_cut;
+        }
+    }
     pub fn hit_ground(&mut self) {
-        if let State::InTheAir(_) = self.state {
+        self.ticks_off_ground = 0;
+        self.rising = false;
+        if self.airborne() {
             self.state = State::Idle;
         }
     }
+    /// For every tick the character does not land on the ground, but touches a wall on the `wall` side.
+    pub fn touch_wall(&mut self, physics: &Physics, wall: Direction) {
+        self.leave_ground(physics);
+        if self.airborne() {
+            self.state = State::WallSliding(wall);
+        }
+    }
+    /// Sliding down a wall is slower than falling.
+    pub fn slide(&self, physics: &Physics, velocity: &mut Velocity) {
+        if let State::WallSliding(_) = self.state {
+            velocity.y = velocity.y.max(-physics.wall_slide_speed);
+        }
+    }
+    /// For every tick the character does not land on the ground or touch a wall.
+    /// Walking off a ledge uses up the jump from the ground, once the coyote time is over.
+    pub fn leave_ground(&mut self, physics: &Physics) {
+        match self.state {
+            State::InTheAir(_) => return,
+            State::WallSliding(_) => {
+                self.state = State::InTheAir(1);
+                return;
+            },
+            State::Idle | State::Walking => {},
+        }
+        self.ticks_off_ground += 1;
+        if self.ticks_off_ground > ticks(physics.coyote_time) {
+            self.state = State::InTheAir(1);
+        }
+    }
     pub fn update_spritesheet(&mut self) -> Option<SpriteType> {
-        if self.state == self.previous_state {
+        if Some(self.state) == self.previous_state {
             return None;
         }
-        self.previous_state = self.state;
-        let spritesheet = match self.state {
-            State::Idle => SpriteType::IdleGreen,
-            State::Walking => SpriteType::WalkGreen,
-            State::InTheAir(_) => SpriteType::JumpGreen,
+        self.previous_state = Some(self.state);
+        let spritesheet = match (self.skin, self.animation()) {
+            (Skin::Green, Animation::Idle) => SpriteType::IdleGreen,
+            (Skin::Green, Animation::Walking) => SpriteType::WalkGreen,
+            (Skin::Green, Animation::Jumping) => SpriteType::JumpGreen,
+            (Skin::Blue, Animation::Idle) => SpriteType::IdleBlue,
+            (Skin::Blue, Animation::Walking) => SpriteType::WalkBlue,
+            (Skin::Blue, Animation::Jumping) => SpriteType::JumpBlue,
         };
         Some(spritesheet)
     }
@@ -1,15 +1,103 @@
 use std::collections::VecDeque;
+use std::fmt;
+use std::str::FromStr;
 
 use bevy::prelude::*;
 
+use super::direction::Direction;
+use super::map::Map;
+use super::parse_error::{MapParseError, MapParseErrorReason, MapParseErrors};
+use super::player::{Animation, Skin};
+
+use crate::options::Difficulty;
 
 #[derive(Component, Deref, DerefMut)]
 pub struct InitialPosition(pub Vec3);
 
+/// Where a character was at some point of a run, and what it was doing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sample {
+    pub position: Vec3,
+    pub animation: Animation,
+    pub facing: Direction,
+}
+
+/// Who made a run, and on what. Everything is optional, old positions files have none of it.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GhostInfo {
+    pub level: String,
+    /// `Map::hash` of the map the run was made on.
+    pub map_hash: Option<u64>,
+    /// `Physics::hash` of the physics a replay was recorded with.
+    pub physics_hash: Option<u64>,
+    pub difficulty: Option<Difficulty>,
+    pub player: String,
+    /// Seconds.
+    pub time: Option<f32>,
+    pub skin: Option<Skin>,
+}
+
+impl GhostInfo {
+    /// Reads one of the header entries shared by every kind of ghost file.
+    pub fn read_entry(&mut self, key: &str, value: &str) -> Result<(), MapParseErrorReason> {
+        match key {
+            "level" => self.level = value.to_string(),
+            "map_hash" => self.map_hash = Some(u64::from_str_radix(value, 16).map_err(|_| MapParseErrorReason::InvalidValue)?),
+            "physics_hash" => self.physics_hash = Some(u64::from_str_radix(value, 16).map_err(|_| MapParseErrorReason::InvalidValue)?),
+            "difficulty" => self.difficulty = Some(Difficulty::from_str(value).map_err(|_| MapParseErrorReason::InvalidValue)?),
+            "player" => self.player = value.to_string(),
+            "time" => self.time = Some(f32::from_str(value).map_err(|_| MapParseErrorReason::InvalidValue)?),
+            "skin" => self.skin = Some(Skin::from_str(value).map_err(|_| MapParseErrorReason::InvalidValue)?),
+            _ => return Err(MapParseErrorReason::UnknownHeaderKey),
+        }
+        Ok(())
+    }
+    pub fn write_entries(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.level.is_empty() {
+            writeln!(f, "level: {}", self.level)?;
+        }
+        if let Some(map_hash) = self.map_hash {
+            writeln!(f, "map_hash: {:016x}", map_hash)?;
+        }
+        if let Some(physics_hash) = self.physics_hash {
+            writeln!(f, "physics_hash: {:016x}", physics_hash)?;
+        }
+        if let Some(difficulty) = self.difficulty {
+            writeln!(f, "difficulty: {}", difficulty)?;
+        }
+        if !self.player.is_empty() {
+            writeln!(f, "player: {}", self.player)?;
+        }
+        if let Some(time) = self.time {
+            writeln!(f, "time: {}", time)?;
+        }
+        if let Some(skin) = self.skin {
+            writeln!(f, "skin: {}", skin)?;
+        }
+        Ok(())
+    }
+    /// Runs made on another version of the map would go through walls, so they are not used.
+    /// Runs that don't know their map are trusted.
+    pub fn fits(&self, map: &Map) -> bool {
+        self.map_hash.map_or(true, |hash| hash == map.hash())
+    }
+}
+
+/// How to fill in the positions between two samples.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Goes through every sample, and follows the curves of jumps instead of cutting their corners.
+    #[default]
+    CatmullRom,
+}
+
+/// A run, sampled every time the timer finishes.
 #[derive(Clone, Component, Debug)]
 pub struct Positions {
-    pub values: VecDeque::<Vec3>,
+    pub values: VecDeque::<Sample>,
     pub timer: Timer,
+    pub info: GhostInfo,
 }
 
 impl Default for Positions {
@@ -17,6 +105,260 @@ impl Default for Positions {
         Self {
             values: VecDeque::with_capacity(1000),
             timer: Timer::from_seconds(0.1, true),
+            info: GhostInfo::default(),
         }
     }
 }
+
+impl Positions {
+    /// The version written by `Display`, older ones can still be read.
+    pub const VERSION: u32 = 2;
+    pub const SEPARATOR: &'static str = "---";
+
+    /// Seconds between two samples.
+    pub fn interval(&self) -> f32 {
+        self.timer.duration().as_secs_f32()
+    }
+    /// Files are sampled from an interval into the run, when the timer first finished,
+    /// so the run is played back from `position` to keep every sample on time.
+    pub fn starting_at(mut self, position: Vec3) -> Self {
+        let facing = self.values.front().map_or_else(Direction::default, |sample| sample.facing);
+        self.values.push_front(Sample { position, animation: Animation::Idle, facing });
+        self
+    }
+    /// Seconds from the first sample to the last.
+    pub fn duration(&self) -> f32 {
+        self.values.len().saturating_sub(1) as f32 * self.interval()
+    }
+    /// Where the run was `time` seconds after its first sample, between the two samples around it.
+    /// The animation and facing are the ones of the sample before.
+    pub fn sample_at(&self, time: f32, interpolation: Interpolation) -> Option<Sample> {
+        let last = self.values.len().checked_sub(1)?;
+        let t = (time / self.interval()).clamp(0.0, last as f32);
+        let previous = (t as usize).min(last.saturating_sub(1));
+        let proportion = t - previous as f32;
+        let point = |i: isize| self.values[i.clamp(0, last as isize) as usize].position;
+        let i = previous as isize;
+        let position = match interpolation {
+            Interpolation::Linear => point(i).lerp(point(i + 1), proportion),
+            Interpolation::CatmullRom => catmull_rom([point(i - 1), point(i), point(i + 1), point(i + 2)], proportion),
+        };
+        let current = self.values[t as usize];
+        Some(Sample {
+            position,
+            ..current
+        })
+    }
+}
+
+/// The point at `t`, from 0 to 1, on the curve between `p1` and `p2`.
+fn catmull_rom([p0, p1, p2, p3]: [Vec3; 4], t: f32) -> Vec3 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+/// Writes a version 2 positions file, which `read_positions_from_file` reads back.
+impl fmt::Display for Positions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "version: {}", Self::VERSION)?;
+        writeln!(f, "interval: {}", self.timer.duration().as_secs_f32())?;
+        self.info.write_entries(f)?;
+        writeln!(f, "{}", Self::SEPARATOR)?;
+        for sample in self.values.iter() {
+            let position = sample.position;
+            writeln!(f, "{},{},{},{},{}", position.x, position.y, position.z, sample.animation, sample.facing)?;
+        }
+        Ok(())
+    }
+}
+
+/// `x,y,z` or `x,y,z,animation,facing`.
+fn read_sample(line: &str) -> Option<Sample> {
+    let fields = Vec::from_iter(line.split(',').map(str::trim));
+    let (x, y, z, animation, facing) = match fields[..] {
+        [x, y, z] => (x, y, z, "Idle", "Right"),
+        [x, y, z, animation, facing] => (x, y, z, animation, facing),
+        _ => return None,
+    };
+    Some(Sample {
+        position: Vec3::new(f32::from_str(x).ok()?, f32::from_str(y).ok()?, f32::from_str(z).ok()?),
+        animation: Animation::from_str(animation).ok()?,
+        facing: Direction::from_str(facing).ok()?,
+    })
+}
+
+/// A positions file is a list of samples, one per line.
+/// Since version 2 it starts with `key: value` entries describing the run, ended by a `---` line:
+/// ```text
+/// version: 2
+/// level: 1
+/// map_hash: 5f1d8c0e6a3b2c47
+/// interval: 0.1
+/// difficulty: Medium
+/// player: Chaz
+/// time: 12.4
+/// skin: Green
+/// ---
+/// -306,-92.98272,1.5,Idle,Right
+/// ```
+/// Version 1 files only have `x,y,z` samples, and maybe an `interval` entry.
+pub fn read_positions_from_file(file: &str, pos: &str) -> Result<Positions, MapParseErrors> {
+    let mut errors = MapParseErrors::default();
+    let mut positions = Positions::default();
+    let lines = pos.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && *line != Positions::SEPARATOR);
+    for (i, line) in lines {
+        let mut error = |token: &str, reason| {
+            errors.push(MapParseError::new(file, Some(i), Some(token), reason));
+        };
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => {
+                match read_sample(line) {
+                    Some(sample) => positions.values.push_back(sample),
+                    None => error(line, MapParseErrorReason::InvalidPosition),
+                }
+                continue;
+            },
+        };
+        match key {
+            "version" => match u32::from_str(value) {
+                Ok(version) if version <= Positions::VERSION => {},
+                Ok(version) => error(value, MapParseErrorReason::UnsupportedVersion { found: version, supported: Positions::VERSION }),
+                Err(_) => error(value, MapParseErrorReason::InvalidValue),
+            },
+            "interval" => match f32::from_str(value) {
+                Ok(interval) if interval > 0.0 => positions.timer = Timer::from_seconds(interval, true),
+                _ => error(value, MapParseErrorReason::InvalidValue),
+            },
+            _ => match positions.info.read_entry(key, value) {
+                Ok(()) => {},
+                Err(MapParseErrorReason::UnknownHeaderKey) => error(key, MapParseErrorReason::UnknownHeaderKey),
+                Err(reason) => error(value, reason),
+            },
+        }
+    }
+    errors.into_result(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::map::read_map;
+
+    fn run() -> Positions {
+        let mut positions = Positions {
+            info: GhostInfo {
+                level: "1".to_string(),
+                map_hash: Some(0x5f1d8c0e6a3b2c47),
+                physics_hash: Some(0x0123456789abcdef),
+                difficulty: Some(Difficulty::Hard),
+                player: "Chaz".to_string(),
+                time: Some(0.1),
+                skin: Some(Skin::Blue),
+            },
+            ..Default::default()
+        };
+        positions.values.extend([
+            Sample { position: Vec3::new(-306.0, -92.5, 1.5), animation: Animation::Walking, facing: Direction::Right },
+            Sample { position: Vec3::new(-300.0, -80.25, 1.5), animation: Animation::Jumping, facing: Direction::Left },
+        ]);
+        positions
+    }
+
+    #[test]
+    fn positions_read_back_what_they_write() {
+        let positions = run();
+        let read = read_positions_from_file("test", &positions.to_string()).unwrap();
+        assert_eq!(read.values, positions.values);
+        assert_eq!(read.info, positions.info);
+        assert_eq!(read.interval(), positions.interval());
+    }
+
+    #[test]
+    fn version_1_files_only_have_positions() {
+        let read = read_positions_from_file("test", "-306,-92.5,1.5\n-300,-80.25,1.5\n").unwrap();
+        assert_eq!(read.values.len(), 2);
+        assert_eq!(read.values[1].position, Vec3::new(-300.0, -80.25, 1.5));
+        assert_eq!(read.info, GhostInfo::default());
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let errors = read_positions_from_file("test", "version: 3\n---\n").unwrap_err();
+        assert_eq!(errors.0, vec![
+            MapParseError::new("test", Some(1), Some("3"), MapParseErrorReason::UnsupportedVersion { found: 3, supported: 2 }),
+        ]);
+    }
+
+    /// Samples going right by 10 pixels every interval.
+    fn straight_line(count: usize) -> Positions {
+        let mut positions = Positions::default();
+        positions.values.extend((0..count).map(|i| Sample {
+            position: Vec3::new(10.0 * i as f32, 0.0, 0.0),
+            animation: Animation::Walking,
+            facing: Direction::Right,
+        }));
+        positions
+    }
+
+    fn x_at(positions: &Positions, time: f32, interpolation: Interpolation) -> f32 {
+        positions.sample_at(time, interpolation).unwrap().position.x
+    }
+
+    #[test]
+    fn samples_are_an_interval_apart() {
+        let positions = straight_line(4);
+        let interval = positions.interval();
+        assert!((positions.duration() - 3.0 * interval).abs() < 1e-6);
+        assert_eq!(x_at(&positions, 2.0 * interval, Interpolation::Linear), 20.0);
+        assert!((x_at(&positions, 1.5 * interval, Interpolation::Linear) - 15.0).abs() < 1e-3);
+        // before the start and after the end, the rival waits on the first or last sample
+        assert_eq!(x_at(&positions, -1.0, Interpolation::Linear), 0.0);
+        assert_eq!(x_at(&positions, 10.0, Interpolation::Linear), 30.0);
+    }
+
+    #[test]
+    fn files_are_played_back_from_the_start_of_the_run() {
+        let positions = straight_line(2).starting_at(Vec3::new(-10.0, 0.0, 0.0));
+        let interval = positions.interval();
+        assert!((positions.duration() - 2.0 * interval).abs() < 1e-6);
+        assert_eq!(x_at(&positions, 0.0, Interpolation::Linear), -10.0);
+        assert_eq!(x_at(&positions, interval, Interpolation::Linear), 0.0);
+    }
+
+    #[test]
+    fn catmull_rom_goes_through_every_sample() {
+        let points = [Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 0.0), Vec3::new(3.0, 3.0, 0.0), Vec3::new(4.0, 1.0, 0.0)];
+        assert!(catmull_rom(points, 0.0).abs_diff_eq(points[1], 1e-5));
+        assert!(catmull_rom(points, 1.0).abs_diff_eq(points[2], 1e-5));
+        // and rounds the corner between them, unlike a straight line
+        assert!(catmull_rom(points, 0.5).y > points[1].lerp(points[2], 0.5).y);
+    }
+
+    #[test]
+    fn catmull_rom_keeps_straight_lines_straight() {
+        let positions = straight_line(4);
+        let interval = positions.interval();
+        assert!((x_at(&positions, 1.5 * interval, Interpolation::CatmullRom) - 15.0).abs() < 1e-3);
+        // the ends repeat the first and last samples
+        assert!((x_at(&positions, 0.5 * interval, Interpolation::CatmullRom) - 5.0).abs() < 1.0);
+    }
+
+    #[test]
+    fn runs_made_on_another_version_of_the_map_do_not_fit() {
+        let map = read_map("test", "Ground Player\nGround Win\n", None).unwrap();
+        let edited = read_map("test", "Ground Player\nGround Empty\nGround Win\n", None).unwrap();
+        let info = GhostInfo { map_hash: Some(map.hash()), ..Default::default() };
+        assert!(info.fits(&map));
+        assert!(!info.fits(&edited));
+        // old files do not know their map
+        assert!(GhostInfo::default().fits(&edited));
+    }
+}
@@ -0,0 +1,69 @@
+use std::path::{Path, PathBuf};
+
+use super::levels::LevelInfo;
+use super::map::positions_path;
+use super::ghosts::Ghost;
+
+use crate::asset_files;
+use crate::log::console_log;
+
+/// Where runs finished in training mode are written, to become the rival of their level.
+/// Nothing is recorded unless the game is told to with `from_args`,
+/// so the rival runs shipped in the assets are only replaced on purpose.
+#[derive(Default)]
+pub struct Recorder {
+    pub directory: Option<PathBuf>,
+}
+
+impl Recorder {
+    const FLAG: &'static str = "--record";
+    const DIRECTORY_FLAG: &'static str = "--record-dir";
+    const VARIABLE: &'static str = "CHAZ_RECORD_DIR";
+    const ASSET_DIRECTORY: &'static str = "positions";
+
+    /// Records to `--record-dir <dir>` or the `CHAZ_RECORD_DIR` environment variable.
+    /// `--record` alone records over the positions files in `asset_folder`, so the game picks the runs up right away.
+    pub fn from_args(args: impl Iterator<Item = String>, asset_folder: &str) -> Self {
+        let args = Vec::from_iter(args);
+        let directory = args.iter()
+            .skip_while(|arg| *arg != Self::DIRECTORY_FLAG)
+            .nth(1)
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os(Self::VARIABLE).map(PathBuf::from))
+            .or_else(|| args.iter()
+                .any(|arg| arg == Self::FLAG)
+                .then(|| asset_files::resolve(asset_folder, Path::new(Self::ASSET_DIRECTORY))));
+        Recorder { directory }
+    }
+
+    /// Writes the rival run of `level`, named like the files in `assets/positions`.
+    pub fn export(&self, level: &LevelInfo, ghost: &Ghost) {
+        let directory = match &self.directory {
+            Some(directory) => directory,
+            None => return,
+        };
+        let file_name = positions_path(Path::new(&level.map))
+            .and_then(|path| Some(Path::new(&path).file_name()?.to_owned()))
+            .unwrap_or_else(|| format!("{}_positions.txt", level.id).into());
+        let path = directory.join(file_name);
+        match write(&path, &ghost.to_string()) {
+            Ok(()) => console_log!("Recorded the rival of level {} to {}", level.id, path.display()),
+            Err(error) => console_log!("Could not record the rival of level {} to {}: {}", level.id, path.display(), error),
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write(path: &Path, text: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(path, text).map_err(|e| e.to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write(path: &Path, text: &str) -> Result<(), String> {
+    // there is no file system to write to, but the file can still be copied from the console
+    console_log!("{}:\n{}", path.display(), text);
+    Ok(())
+}
@@ -0,0 +1,250 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::prelude::*;
+
+use super::direction::Direction;
+use super::parse_error::{MapParseError, MapParseErrorReason, MapParseErrors};
+use super::physics::Physics;
+use super::player::Character;
+use super::positions::{GhostInfo, Positions};
+use super::tick::TICK_SECONDS;
+use super::velocity::Velocity;
+
+/// What a character is told to do during one tick.
+/// The player's comes from its `Controls`, a replaying rival's from its `Replay`.
+#[derive(Clone, Component, Copy, Debug, Default, PartialEq)]
+pub struct InputFrame {
+    pub left: bool,
+    pub right: bool,
+    /// Only for the tick after the jump key was pressed, not while it is held.
+    pub jump: bool,
+    /// The jump key is down, releasing it cuts a jump short.
+    pub jump_held: bool,
+}
+
+impl InputFrame {
+    /// Nothing pressed, like `default()`, which can not be used in constants.
+    pub const NONE: InputFrame = InputFrame { left: false, right: false, jump: false, jump_held: false };
+
+    pub fn apply(&self, physics: &Physics, character: &mut Character, velocity: &mut Velocity, direction: &mut Direction) {
+        let new_direction = Direction::from_input(self.left, self.right);
+        velocity.update(physics, new_direction, TICK_SECONDS);
+        if let Some(new_direction) = new_direction {
+            *direction = new_direction;
+        }
+        character.update_walk_state(velocity.x);
+        character.update_jump(physics, self.jump, self.jump_held, velocity);
+    }
+}
+
+/// `LRJH` for every input that is on, `-` for none.
+impl fmt::Display for InputFrame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flags = [
+            (self.left, 'L'),
+            (self.right, 'R'),
+            (self.jump, 'J'),
+            (self.jump_held, 'H'),
+        ];
+        let flags = String::from_iter(flags.into_iter().filter(|&(on, _)| on).map(|(_, flag)| flag));
+        if flags.is_empty() {
+            write!(f, "-")
+        } else {
+            write!(f, "{}", flags)
+        }
+    }
+}
+
+impl FromStr for InputFrame {
+
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<InputFrame, Self::Err> {
+        let mut frame = InputFrame::default();
+        if input == "-" {
+            return Ok(frame);
+        }
+        for flag in input.chars() {
+            match flag {
+                'L' => frame.left = true,
+                'R' => frame.right = true,
+                'J' => frame.jump = true,
+                'H' => frame.jump_held = true,
+                _ => return Err(()),
+            }
+        }
+        Ok(frame)
+    }
+}
+
+/// A run, as the inputs of every tick from where it started, and nothing else.
+/// Played back through the same systems as the player, so the rival does exactly what the player did:
+/// stomping, being knocked back and respawning happen to it as they happened to the player.
+#[derive(Clone, Component, Debug, Default)]
+pub struct Replay {
+    pub start: Vec2,
+    pub inputs: Vec<InputFrame>,
+    pub info: GhostInfo,
+    /// How many inputs were played back already.
+    pub played: usize,
+}
+
+impl Replay {
+    /// The version written by `Display`, and the only one read. Versions 1 and 2 are positions files.
+    pub const VERSION: u32 = 3;
+    /// The first version that is a replay, earlier ones are positions files.
+    pub const FIRST_VERSION: u32 = 3;
+
+    pub fn next(&mut self) -> Option<InputFrame> {
+        let frame = self.inputs.get(self.played).copied();
+        self.played += 1;
+        frame
+    }
+    pub fn finished(&self) -> bool {
+        self.played >= self.inputs.len()
+    }
+    /// How long the run takes, in seconds.
+    pub fn duration(&self) -> f32 {
+        self.inputs.len() as f32 * TICK_SECONDS
+    }
+}
+
+/// Writes a replay file, which `read_replay_from_file` reads back.
+/// Inputs are run-length encoded, as they rarely change from one tick to the next.
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "version: {}", Self::VERSION)?;
+        writeln!(f, "tick: {}", TICK_SECONDS)?;
+        writeln!(f, "start: {},{}", self.start.x, self.start.y)?;
+        self.info.write_entries(f)?;
+        writeln!(f, "{}", Positions::SEPARATOR)?;
+        let mut inputs = self.inputs.iter().peekable();
+        while let Some(frame) = inputs.next() {
+            let mut count = 1;
+            while inputs.next_if_eq(&frame).is_some() {
+                count += 1;
+            }
+            writeln!(f, "{} {}", count, frame)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_start(value: &str) -> Option<Vec2> {
+    let (x, y) = value.split_once(',')?;
+    Some(Vec2::new(f32::from_str(x.trim()).ok()?, f32::from_str(y.trim()).ok()?))
+}
+
+/// `count flags`, e.g. `12 RJ`.
+fn read_inputs(line: &str) -> Option<(usize, InputFrame)> {
+    let (count, frame) = line.split_once(' ')?;
+    Some((usize::from_str(count).ok()?, InputFrame::from_str(frame.trim()).ok()?))
+}
+
+/// A replay file has the same `key: value` entries as a positions file, then `---`,
+/// then one line per run of identical inputs:
+/// ```text
+/// version: 3
+/// tick: 0.016666668
+/// start: -306,-92.98272
+/// level: 1
+/// physics_hash: 9b2e61a0c4d7f385
+/// ---
+/// 40 R
+/// 1 RJH
+/// 20 RH
+/// 25 -
+/// ```
+/// Replays only make sense at the tick they were recorded at, so other ticks are rejected.
+pub fn read_replay_from_file(file: &str, text: &str) -> Result<Replay, MapParseErrors> {
+    let mut errors = MapParseErrors::default();
+    let mut replay = Replay::default();
+    let lines = text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && *line != Positions::SEPARATOR);
+    for (i, line) in lines {
+        let mut error = |token: &str, reason| {
+            errors.push(MapParseError::new(file, Some(i), Some(token), reason));
+        };
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => {
+                match read_inputs(line) {
+                    Some((count, frame)) => replay.inputs.extend(std::iter::repeat(frame).take(count)),
+                    None => error(line, MapParseErrorReason::InvalidInput),
+                }
+                continue;
+            },
+        };
+        match key {
+            "version" => match u32::from_str(value) {
+                Ok(version) if version == Replay::VERSION => {},
+                Ok(version) => error(value, MapParseErrorReason::UnsupportedVersion { found: version, supported: Replay::VERSION }),
+                Err(_) => error(value, MapParseErrorReason::InvalidValue),
+            },
+            "tick" => match f32::from_str(value) {
+                Ok(tick) if (tick - TICK_SECONDS).abs() < 1e-6 => {},
+                _ => error(value, MapParseErrorReason::InvalidValue),
+            },
+            "start" => match read_start(value) {
+                Some(start) => replay.start = start,
+                None => error(value, MapParseErrorReason::InvalidValue),
+            },
+            _ => match replay.info.read_entry(key, value) {
+                Ok(()) => {},
+                Err(MapParseErrorReason::UnknownHeaderKey) => error(key, MapParseErrorReason::UnknownHeaderKey),
+                Err(reason) => error(value, reason),
+            },
+        }
+    }
+    errors.into_result(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(flags: &str) -> InputFrame {
+        InputFrame::from_str(flags).unwrap()
+    }
+
+    #[test]
+    fn replays_read_back_what_they_write() {
+        let mut replay = Replay {
+            start: Vec2::new(-306.0, -92.98272),
+            info: GhostInfo {
+                level: "1".to_string(),
+                map_hash: Some(0x5f1d8c0e6a3b2c47),
+                physics_hash: Some(0x0123456789abcdef),
+                player: "Chaz".to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        for (count, flags) in [(40, "R"), (1, "RJH"), (20, "RH"), (25, "-"), (3, "LH")] {
+            replay.inputs.extend(std::iter::repeat(frame(flags)).take(count));
+        }
+        let read = read_replay_from_file("test", &replay.to_string()).unwrap();
+        assert_eq!(read.start, replay.start);
+        assert_eq!(read.inputs, replay.inputs);
+        assert_eq!(read.info, replay.info);
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let errors = read_replay_from_file("test", "version: 4\n---\n").unwrap_err();
+        assert_eq!(errors.0, vec![
+            MapParseError::new("test", Some(1), Some("4"), MapParseErrorReason::UnsupportedVersion { found: 4, supported: 3 }),
+        ]);
+    }
+
+    #[test]
+    fn inputs_are_only_keys() {
+        let errors = read_replay_from_file("test", "version: 3\n---\n2 R\n1 RS\n").unwrap_err();
+        assert_eq!(errors.0, vec![
+            MapParseError::new("test", Some(4), Some("1 RS"), MapParseErrorReason::InvalidInput),
+        ]);
+    }
+}
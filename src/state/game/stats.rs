@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::prelude::*;
+use enum_iterator::IntoEnumIterator;
+
+use crate::camera::MainCamera;
+use crate::log::console_log;
+use crate::options::Difficulty;
+use crate::storage;
+
+use super::levels::Levels;
+
+/// Something for the player to pick up, counted in the `LevelStats`.
+#[derive(Component)]
+pub struct Collectible;
+
+/// What the player did in a level: during a run, or the best of the runs that won it.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct LevelStats {
+    pub collected: u32,
+    /// How many collectibles the level has.
+    pub total: u32,
+    /// Seconds to reach the win tile, only known once the level is won.
+    pub time: f32,
+    pub stomped: u32,
+}
+
+impl LevelStats {
+    /// The best of both, value by value, so they may come from different runs.
+    /// The total is the one of `run`, in case the map changed since.
+    pub fn best(self, run: LevelStats) -> Self {
+        LevelStats {
+            collected: self.collected.max(run.collected),
+            total: run.total,
+            time: self.time.min(run.time),
+            stomped: self.stomped.max(run.stomped),
+        }
+    }
+}
+
+impl fmt::Display for LevelStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{} collected in {:.2}s, {} stomped", self.collected, self.total, self.time, self.stomped)
+    }
+}
+
+/// The best stats of every level won, by level id and difficulty.
+#[derive(Default)]
+pub struct BestStats(pub HashMap<(String, Difficulty), LevelStats>);
+
+impl BestStats {
+    pub fn get(&self, level: &str, difficulty: Difficulty) -> Option<&LevelStats> {
+        self.0.get(&(level.to_string(), difficulty))
+    }
+
+    /// Keeps the best of `run` and the earlier runs, and saves them.
+    pub fn record(&mut self, level: &str, difficulty: Difficulty, run: LevelStats) {
+        let best = match self.get(level, difficulty) {
+            Some(best) => best.best(run),
+            None => run,
+        };
+        self.0.insert((level.to_string(), difficulty), best);
+        let saved = ron::ser::to_string(&best)
+            .map_err(|error| error.to_string())
+            .and_then(|text| storage::save(&storage_key(level, difficulty), &text));
+        if let Err(error) = saved {
+            console_log!("Could not save the stats of level {}: {}", level, error);
+        }
+    }
+}
+
+fn storage_key(level: &str, difficulty: Difficulty) -> String {
+    format!("stats/{}_{}.ron", level, difficulty)
+}
+
+/// Brings back the stats of earlier sessions, once the levels are known.
+pub fn load_best_stats(levels: Res<Levels>, mut best_stats: ResMut<BestStats>) {
+    for level in levels.iter() {
+        for difficulty in Difficulty::into_enum_iter() {
+            let text = match storage::load(&storage_key(&level.id, difficulty)) {
+                Some(text) => text,
+                None => continue,
+            };
+            match ron::de::from_str(&text) {
+                Ok(stats) => {
+                    best_stats.0.insert((level.id.clone(), difficulty), stats);
+                },
+                Err(error) => console_log!("Ignoring the {} stats of level {}: {}", difficulty, level.id, error),
+            }
+        }
+    }
+}
+
+/// The collectibles picked up so far, under the hearts.
+#[derive(Component)]
+pub struct CollectedCounter;
+
+fn collected_text(stats: &LevelStats) -> String {
+    format!("Collected {}/{}", stats.collected, stats.total)
+}
+
+pub fn spawn_collected_counter(
+    mut commands: Commands,
+    windows: Res<Windows>,
+    stats: Res<LevelStats>,
+    asset_server: Res<AssetServer>,
+    camera_query: Query<Entity, With<MainCamera>>,
+) {
+    if stats.total == 0 {
+        return;
+    }
+    let window = windows.get_primary().unwrap();
+    let text_style = TextStyle {
+        font: asset_server.load("kenney-fonts/Fonts/Kenney Blocks.ttf"),
+        font_size: 20.0,
+        color: Color::WHITE,
+    };
+    let text_alignment = TextAlignment {
+        vertical: VerticalAlign::Center,
+        horizontal: HorizontalAlign::Left,
+    };
+    commands.entity(camera_query.single()).with_children(|camera| {
+        camera
+            .spawn_bundle(Text2dBundle {
+                text: Text::with_section(collected_text(&stats), text_style, text_alignment),
+                transform: Transform::from_translation(Vec3::new(-window.width() / 2.0 + 12.0, window.height() / 2.0 - 45.0, -10.0)),
+                ..Default::default()
+            })
+            .insert(CollectedCounter);
+    });
+}
+
+pub fn update_collected_counter(stats: Res<LevelStats>, mut query: Query<&mut Text, With<CollectedCounter>>) {
+    if !stats.is_changed() {
+        return;
+    }
+    for mut text in query.iter_mut() {
+        text.sections[0].value = collected_text(&stats);
+    }
+}
+
+pub fn despawn_collected_counter(mut commands: Commands, query: Query<Entity, With<CollectedCounter>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_best_of_each_value_is_kept_from_any_run() {
+        let best = LevelStats { collected: 3, total: 4, time: 12.5, stomped: 0 };
+        let run = LevelStats { collected: 1, total: 5, time: 10.0, stomped: 2 };
+        assert_eq!(best.best(run), LevelStats { collected: 3, total: 5, time: 10.0, stomped: 2 });
+    }
+}
@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+
+use super::outcome::GameOutcome;
+
+/// Gameplay advances in steps of this many seconds, however long frames take,
+/// so the same inputs always make the same run.
+pub const TICK_SECONDS: f32 = 1.0 / 60.0;
+
+/// The steps of a frame in `AppState::Game` that depend on each other, in the order they run.
+/// Everything else only draws what the ticks did.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, SystemLabel)]
+pub enum FrameStep {
+    /// Keys are read into the player's `InputFrame`.
+    Controls,
+    /// As many ticks as fit in the frame run.
+    Ticks,
+    /// The level ends if a tick decided how.
+    Outcome,
+}
+
+/// The steps of a tick, in the order they run.
+#[derive(Clone, Debug, Eq, Hash, PartialEq, SystemLabel)]
+pub enum TickStep {
+    /// Every character gets the inputs of the tick, from its controls or its replay.
+    Input,
+    /// The inputs change the velocities.
+    Velocity,
+    /// Everything moves.
+    Movement,
+    /// Colliders are sorted into the cells of the `ColliderGrid`.
+    Broadphase,
+    /// `CollisionEvent`s are sent.
+    Collision,
+    /// Collisions have their effects, and the game is won or lost.
+    Checks,
+}
+
+/// The systems that run once per tick, and the time they still have to catch up on.
+pub struct GameTicks {
+    stage: SystemStage,
+    accumulator: f32,
+}
+
+impl GameTicks {
+    /// After a long frame, e.g. when the game was in a background tab, the rest is skipped
+    /// instead of running hundreds of ticks at once.
+    const MAX_TICKS_PER_FRAME: u32 = 10;
+
+    pub fn new(stage: SystemStage) -> Self {
+        GameTicks {
+            stage,
+            accumulator: 0.0,
+        }
+    }
+    /// Runs a single tick, whatever the time.
+    pub fn tick(&mut self, world: &mut World) {
+        self.stage.run(world);
+    }
+    /// Runs as many ticks as fit in a frame of `seconds`, and in the time left over from the frames before.
+    /// Stops as soon as the outcome of the level is decided, so nothing happens after it.
+    pub fn advance(&mut self, world: &mut World, seconds: f32) {
+        self.accumulator += seconds;
+        let mut count = 0;
+        while self.accumulator >= TICK_SECONDS {
+            if world.resource::<GameOutcome>().is_decided() {
+                self.accumulator = 0.0;
+                break;
+            }
+            if count == Self::MAX_TICKS_PER_FRAME {
+                self.accumulator = 0.0;
+                break;
+            }
+            self.tick(world);
+            self.accumulator -= TICK_SECONDS;
+            count += 1;
+        }
+    }
+}
+
+/// Runs the ticks of the time since the last frame.
+pub fn run_ticks(world: &mut World) {
+    let delta = world.resource::<Time>().delta_seconds();
+    world.resource_scope(|world, mut ticks: Mut<GameTicks>| ticks.advance(world, delta));
+}
+
+pub fn reset_ticks(mut ticks: ResMut<GameTicks>) {
+    ticks.accumulator = 0.0;
+}
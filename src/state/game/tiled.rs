@@ -0,0 +1,367 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bevy::asset::{AssetLoader, LoadContext, LoadedAsset};
+use bevy::utils::BoxedFuture;
+
+use super::enemies::Patrol;
+use super::map::{import_map, positions_path, Map, Tile};
+use super::parse_error::{MapParseError, MapParseErrorReason, MapParseErrors};
+
+/// The parts of a [Tiled](https://www.mapeditor.org) map that matter to us,
+/// read from either the JSON (`.tmj`) or the XML (`.tmx`) format.
+///
+/// Every tile in a tile layer becomes `Ground`, the game picks the right image on its own.
+/// Objects become the tile named by their class (or type, or name): `Player`, `Rival`, `Win`,
+/// `Blue`, `Jeremy`, `Blocky`, `Checkpoint`, `Coin` or `Gem`. Enemies can have `speed` and `amplitude` properties.
+/// Map properties are read like the header of a map file.
+#[derive(Debug, Deserialize)]
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tilewidth: f32,
+    tileheight: f32,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+    #[serde(default)]
+    layers: Vec<TiledLayer>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledLayer {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    data: Vec<u32>,
+    #[serde(default)]
+    objects: Vec<TiledObject>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledObject {
+    #[serde(default)]
+    name: String,
+    #[serde(default, rename = "type")]
+    kind: String,
+    #[serde(default)]
+    class: String,
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    width: f32,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    gid: Option<u32>,
+    #[serde(default)]
+    properties: Vec<TiledProperty>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TiledProperty {
+    name: String,
+    value: serde_json::Value,
+}
+
+impl TiledProperty {
+    fn value(&self) -> String {
+        match &self.value {
+            serde_json::Value::String(value) => value.clone(),
+            value => value.to_string(),
+        }
+    }
+}
+
+impl TiledObject {
+    fn tile_name(&self) -> &str {
+        [&self.class, &self.kind, &self.name].into_iter()
+            .find(|name| !name.is_empty())
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+    /// The center of the object, in pixels from the top left corner of the map.
+    fn center(&self) -> (f32, f32) {
+        let y = if self.gid.is_some() {
+            // tile objects are anchored on their bottom left corner
+            self.y - self.height / 2.0
+        } else {
+            self.y + self.height / 2.0
+        };
+        (self.x + self.width / 2.0, y)
+    }
+    fn property(&self, name: &str) -> Option<String> {
+        self.properties.iter().find(|property| property.name == name).map(TiledProperty::value)
+    }
+}
+
+fn invalid(file: &str, token: &str) -> MapParseError {
+    MapParseError::new(file, None, Some(token), MapParseErrorReason::InvalidTiledMap)
+}
+
+fn read_json(file: &str, text: &str) -> Result<TiledMap, MapParseErrors> {
+    serde_json::from_str(text).map_err(|e| MapParseErrors(vec![invalid(file, &e.to_string())]))
+}
+
+fn xml_error(file: &str, token: &str) -> MapParseErrors {
+    MapParseErrors(vec![invalid(file, token)])
+}
+
+fn xml_number(file: &str, node: roxmltree::Node, name: &str) -> Result<f32, MapParseErrors> {
+    let value = node.attribute(name)
+        .ok_or_else(|| xml_error(file, &format!("missing attribute `{}` in <{}>", name, node.tag_name().name())))?;
+    f32::from_str(value).map_err(|_| xml_error(file, value))
+}
+
+fn xml_optional_number(file: &str, node: roxmltree::Node, name: &str) -> Result<f32, MapParseErrors> {
+    match node.attribute(name) {
+        Some(_) => xml_number(file, node, name),
+        None => Ok(0.0),
+    }
+}
+
+fn xml_properties(node: roxmltree::Node) -> Vec<TiledProperty> {
+    node.children()
+        .filter(|child| child.has_tag_name("properties"))
+        .flat_map(|properties| properties.children().filter(|child| child.has_tag_name("property")))
+        .map(|property| TiledProperty {
+            name: property.attribute("name").unwrap_or_default().to_string(),
+            value: serde_json::Value::String(property.attribute("value").or_else(|| property.text()).unwrap_or_default().to_string()),
+        })
+        .collect()
+}
+
+fn xml_object(file: &str, object: roxmltree::Node) -> Result<TiledObject, MapParseErrors> {
+    Ok(TiledObject {
+        name: object.attribute("name").unwrap_or_default().to_string(),
+        kind: object.attribute("type").unwrap_or_default().to_string(),
+        class: object.attribute("class").unwrap_or_default().to_string(),
+        x: xml_number(file, object, "x")?,
+        y: xml_number(file, object, "y")?,
+        width: xml_optional_number(file, object, "width")?,
+        height: xml_optional_number(file, object, "height")?,
+        gid: object.attribute("gid").and_then(|gid| u32::from_str(gid).ok()),
+        properties: xml_properties(object),
+    })
+}
+
+fn read_xml(file: &str, text: &str) -> Result<TiledMap, MapParseErrors> {
+    let document = roxmltree::Document::parse(text).map_err(|e| xml_error(file, &e.to_string()))?;
+    let map = document.root_element();
+    let mut layers = Vec::new();
+    for layer in map.children().filter(|child| child.is_element()) {
+        match layer.tag_name().name() {
+            "layer" => {
+                let data = match layer.children().find(|child| child.has_tag_name("data")) {
+                    Some(data) => data,
+                    None => continue,
+                };
+                if data.attribute("encoding") != Some("csv") {
+                    return Err(xml_error(file, "only CSV encoded tile layers are supported"));
+                }
+                let data = data.text().unwrap_or_default()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|gid| !gid.is_empty())
+                    .map(|gid| u32::from_str(gid).map_err(|_| xml_error(file, gid)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                layers.push(TiledLayer { kind: "tilelayer".to_string(), data, objects: Vec::new() });
+            },
+            "objectgroup" => {
+                let objects = layer.children()
+                    .filter(|child| child.has_tag_name("object"))
+                    .map(|object| xml_object(file, object))
+                    .collect::<Result<Vec<_>, _>>()?;
+                layers.push(TiledLayer { kind: "objectgroup".to_string(), data: Vec::new(), objects });
+            },
+            _ => {},
+        }
+    }
+    Ok(TiledMap {
+        width: xml_number(file, map, "width")? as usize,
+        height: xml_number(file, map, "height")? as usize,
+        tilewidth: xml_number(file, map, "tilewidth")?,
+        tileheight: xml_number(file, map, "tileheight")?,
+        properties: xml_properties(map),
+        layers,
+    })
+}
+
+/// Tiled counts rows from the top, our maps count them from the bottom.
+fn convert(file: &str, tiled: TiledMap, positions: Option<(&str, &str)>) -> Result<Map, MapParseErrors> {
+    let mut errors = MapParseErrors::default();
+    let mut values = vec![vec![Tile::Empty; tiled.height]; tiled.width];
+    let mut patrols = HashMap::new();
+    for layer in tiled.layers.iter() {
+        match layer.kind.as_str() {
+            "tilelayer" => {
+                if layer.data.len() != tiled.width * tiled.height {
+                    errors.push(invalid(file, "tile layer size does not match the map size"));
+                    continue;
+                }
+                for (index, &gid) in layer.data.iter().enumerate() {
+                    if gid != 0 {
+                        let (column, row) = (index % tiled.width, index / tiled.width);
+                        values[column][tiled.height - 1 - row] = Tile::Ground;
+                    }
+                }
+            },
+            "objectgroup" => for object in layer.objects.iter() {
+                let name = object.tile_name();
+                let tile = match Tile::from_str(name) {
+                    Ok(tile) => tile,
+                    Err(_) => {
+                        errors.push(MapParseError::new(file, None, Some(name), MapParseErrorReason::UnknownTile));
+                        continue;
+                    },
+                };
+                let (x, y) = object.center();
+                let (column, row) = ((x / tiled.tilewidth).floor(), (y / tiled.tileheight).floor());
+                if column < 0.0 || row < 0.0 || column as usize >= tiled.width || row as usize >= tiled.height {
+                    errors.push(invalid(file, &format!("`{}` is outside of the map", name)));
+                    continue;
+                }
+                let (i, j) = (column as usize, tiled.height - 1 - row as usize);
+                values[i][j] = tile;
+                let default_patrol = match tile {
+                    Tile::Jeremy => Patrol::JEREMY,
+                    Tile::Blocky => Patrol::BLOCKY,
+                    _ => continue,
+                };
+                let mut patrol = default_patrol;
+                for (name, value) in [("speed", &mut patrol.speed), ("amplitude", &mut patrol.amplitude)] {
+                    if let Some(property) = object.property(name) {
+                        match f32::from_str(&property) {
+                            Ok(property) => *value = property,
+                            Err(_) => errors.push(MapParseError::new(file, None, Some(&property), MapParseErrorReason::InvalidValue)),
+                        }
+                    }
+                }
+                if patrol != default_patrol {
+                    patrols.insert((i, j), patrol);
+                }
+            },
+            _ => {},
+        }
+    }
+    let mut header = vec![format!("size: {}x{}", tiled.width, tiled.height)];
+    header.extend(tiled.properties.iter().map(|property| format!("{}: {}", property.name, property.value())));
+    let header = Vec::from_iter(header.iter().map(String::as_str));
+    import_map(file, &header, values, patrols, positions, errors)
+}
+
+pub fn read_tiled_map(file: &str, text: &str, positions: Option<(&str, &str)>) -> Result<Map, MapParseErrors> {
+    let tiled = if file.ends_with(".tmx") {
+        read_xml(file, text)?
+    } else {
+        read_json(file, text)?
+    };
+    convert(file, tiled, positions)
+}
+
+/// Loads Tiled maps as if they were `.chazmap` files.
+#[derive(Default)]
+pub struct TiledLoader;
+
+impl AssetLoader for TiledLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let file = load_context.path().display().to_string();
+            let positions = match positions_path(load_context.path()) {
+                Some(path) => match load_context.read_asset_bytes(&path).await {
+                    Ok(bytes) => Some((path, String::from_utf8(bytes)?)),
+                    Err(_) => None, // a level without a rival
+                },
+                None => None,
+            };
+            let positions = positions.as_ref().map(|(path, text)| (path.as_str(), text.as_str()));
+            let map = read_tiled_map(&file, std::str::from_utf8(bytes)?, positions)?;
+            load_context.set_default_asset(LoadedAsset::new(map));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["tmj", "tmx"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TMJ: &str = r#"{
+        "width": 3, "height": 2, "tilewidth": 18, "tileheight": 18,
+        "properties": [{ "name": "name", "type": "string", "value": "Imported" }],
+        "layers": [
+            { "type": "tilelayer", "data": [0, 0, 0, 1, 1, 1] },
+            { "type": "objectgroup", "objects": [
+                { "class": "Player", "x": 0, "y": 0, "width": 18, "height": 18 },
+                { "type": "Jeremy", "x": 18, "y": 0, "width": 18, "height": 18,
+                  "properties": [{ "name": "speed", "type": "float", "value": 50 }] },
+                { "name": "Win", "gid": 1, "x": 36, "y": 18, "width": 18, "height": 18 }
+            ] }
+        ]
+    }"#;
+
+    const TMX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="3" height="2" tilewidth="18" tileheight="18">
+ <properties>
+  <property name="name" value="Imported"/>
+ </properties>
+ <layer id="1" name="Ground" width="3" height="2">
+  <data encoding="csv">
+0,0,0,
+1,1,1
+</data>
+ </layer>
+ <objectgroup id="2" name="Objects">
+  <object id="1" class="Player" x="0" y="0" width="18" height="18"/>
+  <object id="2" type="Jeremy" x="18" y="0" width="18" height="18">
+   <properties>
+    <property name="speed" type="float" value="50"/>
+   </properties>
+  </object>
+  <object id="3" name="Win" gid="1" x="36" y="18" width="18" height="18"/>
+ </objectgroup>
+</map>
+"#;
+
+    fn assert_imported(map: &Map) {
+        assert_eq!(map.header.name, "Imported");
+        assert_eq!(**map, vec![
+            vec![Tile::Ground, Tile::Player],
+            vec![Tile::Ground, Tile::Jeremy],
+            vec![Tile::Ground, Tile::Win],
+        ]);
+        assert_eq!(map.patrols.get(&(1, 1)), Some(&Patrol { speed: 50.0, ..Patrol::JEREMY }));
+    }
+
+    #[test]
+    fn json_maps_are_imported() {
+        assert_imported(&read_tiled_map("test.tmj", TMJ, None).unwrap());
+    }
+
+    #[test]
+    fn xml_maps_are_imported_like_json_maps() {
+        assert_imported(&read_tiled_map("test.tmx", TMX, None).unwrap());
+    }
+
+    #[test]
+    fn unknown_objects_are_reported() {
+        let tmj = TMJ.replace(r#""class": "Player""#, r#""class": "Lava""#);
+        let errors = read_tiled_map("test.tmj", &tmj, None).err().expect("the map has no player");
+        assert!(errors.0.contains(&MapParseError::new("test.tmj", None, Some("Lava"), MapParseErrorReason::UnknownTile)));
+        assert!(errors.0.iter().any(|error| error.reason == MapParseErrorReason::MissingPlayer));
+    }
+
+    #[test]
+    fn only_csv_tile_layers_are_supported() {
+        let tmx = TMX.replace(r#"encoding="csv""#, r#"encoding="base64""#);
+        let errors = read_tiled_map("test.tmx", &tmx, None).err().expect("the layer is not CSV");
+        assert_eq!(errors.0, vec![invalid("test.tmx", "only CSV encoded tile layers are supported")]);
+    }
+}
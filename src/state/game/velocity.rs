@@ -1,34 +1,32 @@
 use bevy::prelude::*;
 
 use super::direction;
+use super::physics::Physics;
 
 #[derive(Component, Default, Deref, DerefMut)]
 pub struct Velocity(pub Vec3);
 
 impl Velocity {
-    pub fn apply_gravity(&mut self, time: f32) {
-        let max_speed = 300.0;
-        let gravity_acceleration = 500.0;
-        self.y -= gravity_acceleration * time;
-        limit(&mut self.y, max_speed);
-    }
-    fn increase(&mut self, direction: direction::Direction) {
-        let max_speed = 250.0;
-        let speed_increase = 10.0;
-        self.x += speed_increase * f32::from(direction);
-        limit(&mut self.x, max_speed);
-    }
-    fn decrease(&mut self) {
-        if self.x.abs() < 10.0 {
+    pub fn apply_gravity(&mut self, physics: &Physics, time: f32) {
+        self.y -= physics.gravity * time;
+        limit(&mut self.y, physics.terminal_speed);
+    }
+    fn increase(&mut self, physics: &Physics, direction: direction::Direction, time: f32) {
+        self.x += physics.run_acceleration * f32::from(direction) * time;
+        limit(&mut self.x, physics.max_run_speed);
+    }
+    fn decrease(&mut self, physics: &Physics, time: f32) {
+        if self.x.abs() < physics.run_acceleration * time {
             self.x = 0.0
         } else {
-            self.x *= 0.9
+            self.x *= (-physics.friction * time).exp()
         };
     }
-    pub fn update(&mut self, direction: Option<direction::Direction>) {
+    /// Runs faster in `direction` if there is one, slows down otherwise, during `time` seconds.
+    pub fn update(&mut self, physics: &Physics, direction: Option<direction::Direction>, time: f32) {
         match direction {
-            None => self.decrease(),
-            Some(dir) => self.increase(dir),
+            None => self.decrease(physics, time),
+            Some(dir) => self.increase(physics, dir, time),
         }
     }
     pub fn stop_left(&mut self) {
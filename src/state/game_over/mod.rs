@@ -5,6 +5,7 @@ use crate::camera::MainCamera;
 use crate::GameOverEvent;
 
 use crate::state::AppState;
+use crate::state::editor::EditorSession;
 
 pub struct GameOver;
 
@@ -53,6 +54,8 @@ fn show_text(
                     },
                     text_alignment,
                 ),
+                // under the main message
+                transform: Transform::from_translation(Vec3::new(0.0, -130.0, 0.0)),
                 ..Default::default()
             });
         });
@@ -61,6 +64,7 @@ fn show_text(
 
 fn show_buttons(
     state: Res<State<AppState>>,
+    editor_session: Res<EditorSession>,
     mut commands: Commands,
     asset_server: Res<AssetServer>
 ) {
@@ -73,6 +77,12 @@ fn show_buttons(
         text: "Retry",
         action: Action::ChangeState(AppState::Game),
     }.build(&mut commands, &asset_server, &state);
+    if editor_session.playtesting {
+        ButtonBuilder {
+            text: "Edit",
+            action: Action::ChangeState(AppState::Editor),
+        }.build(&mut commands, &asset_server, &state);
+    }
 }
 
 fn cleanup(
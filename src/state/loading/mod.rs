@@ -2,7 +2,11 @@ use bevy::asset::LoadState;
 use bevy::prelude::*;
 
 use crate::camera::MainCamera;
+use crate::log::console_log;
+use crate::options::Options;
 use crate::state::AppState;
+use crate::state::game::levels::{LevelManifest, Levels, MANIFEST_PATH};
+use crate::state::game::physics::{PhysicsConfig, PHYSICS_PATH};
 use crate::sprite::*;
 
 mod loading_bar;
@@ -15,10 +19,13 @@ impl Plugin for Loading {
         app
             .add_state(AppState::PreLoad)
             .add_system_set(SystemSet::on_enter(AppState::PreLoad).with_system(load_preloaded_textures))
+            .add_system_set(SystemSet::on_enter(AppState::PreLoad).with_system(load_level_manifest))
+            .add_system_set(SystemSet::on_enter(AppState::PreLoad).with_system(load_physics_config))
             .add_system_set(SystemSet::on_update(AppState::PreLoad).with_system(check_preloaded_textures))
             .add_system_set(SystemSet::on_enter(AppState::Loading).with_system(spawn_camera))
             .add_system_set(SystemSet::on_enter(AppState::Loading).with_system(setup_loading_bar))
             .add_system_set(SystemSet::on_enter(AppState::Loading).with_system(load_textures))
+            .add_system_set(SystemSet::on_enter(AppState::Loading).with_system(load_maps))
             .add_system_set(SystemSet::on_update(AppState::Loading).with_system(check_textures))
             .add_system_set(SystemSet::on_exit(AppState::Loading).with_system(remove_loading_bar));
     }
@@ -29,13 +36,36 @@ fn load_preloaded_textures(mut sprite_handles: ResMut<SpriteHandles>, asset_serv
     sprite_handles.handles.insert("loading".to_string(), handles);
 }
 
+fn load_level_manifest(mut levels: ResMut<Levels>, asset_server: Res<AssetServer>) {
+    levels.manifest = asset_server.load(MANIFEST_PATH);
+}
+
+/// The handle is kept as a resource, so the config stays loaded and is reloaded when its file changes.
+fn load_physics_config(mut physics_config: ResMut<Handle<PhysicsConfig>>, asset_server: Res<AssetServer>) {
+    *physics_config = asset_server.load(PHYSICS_PATH);
+}
+
 fn check_preloaded_textures(
     mut state: ResMut<State<AppState>>,
     sprite_handles: ResMut<SpriteHandles>,
+    levels: Res<Levels>,
+    physics_config: Res<Handle<PhysicsConfig>>,
     asset_server: Res<AssetServer>,
 ) {
-    let ids = sprite_handles.id_list();
+    // the game still starts without the manifest, with no levels to play,
+    // and without the physics config, with the default physics until the file is fixed
+    let manifest_failed = asset_server.get_load_state(&levels.manifest) == LoadState::Failed;
+    let physics_failed = asset_server.get_load_state(&*physics_config) == LoadState::Failed;
+    let manifest = Some(levels.manifest.id).filter(|_| !manifest_failed);
+    let physics = Some(physics_config.id).filter(|_| !physics_failed);
+    let ids = sprite_handles.id_list().chain(manifest).chain(physics);
     if let LoadState::Loaded = asset_server.get_group_load_state(ids) {
+        if manifest_failed {
+            console_log!("The level manifest {} failed to load, see the warning above for the reason, no level will be playable", MANIFEST_PATH);
+        }
+        if physics_failed {
+            console_log!("The physics config {} failed to load, see the warning above for the reason, the default physics are used", PHYSICS_PATH);
+        }
         state.set(AppState::Loading).unwrap();
     }
 }
@@ -83,14 +113,45 @@ fn load_textures(mut sprite_handles: ResMut<SpriteHandles>, asset_server: Res<As
     }
 }
 
+fn load_maps(
+    mut levels: ResMut<Levels>,
+    manifests: Res<Assets<LevelManifest>>,
+    asset_server: Res<AssetServer>,
+) {
+    // missing if it failed to load during AppState::PreLoad
+    levels.levels = manifests.get(&levels.manifest)
+        .map_or_else(Vec::new, |manifest| manifest.levels.clone());
+    levels.maps = levels.levels.iter()
+        .map(|level| (level.id.clone(), asset_server.load(level.map.as_str())))
+        .collect();
+}
+
 fn check_textures(
     mut state: ResMut<State<AppState>>,
     sprite_handles: ResMut<SpriteHandles>,
+    mut levels: ResMut<Levels>,
+    mut options: ResMut<Options>,
     asset_server: Res<AssetServer>,
     texture_atlases: Res<Assets<TextureAtlas>>,
     mut query: Query<(&LoadingBar, &mut TextureAtlasSprite, &Handle<TextureAtlas>)>,
 ) {
-    let ids = sprite_handles.id_list();
+    // a broken map should not prevent playing the other levels
+    let failed_levels = Vec::from_iter(levels.maps.iter()
+        .filter(|(_, handle)| asset_server.get_load_state(*handle) == LoadState::Failed)
+        .map(|(id, _)| id.clone()));
+    for id in failed_levels {
+        console_log!("Level {} failed to load and will not be playable, see the warning above for the reason", id);
+        levels.maps.remove(&id);
+        levels.levels.retain(|level| level.id != id);
+    }
+    // the level to play may have been one of them, or not be in the manifest at all
+    if levels.get(&options.level).is_none() {
+        if let Some(level) = levels.visible().next() {
+            options.level = level.id.clone();
+        }
+    }
+
+    let ids = sprite_handles.id_list().chain(levels.maps.values().map(|h| h.id));
     let mut total = 0;
     let mut loaded = 0;
     for id in ids {
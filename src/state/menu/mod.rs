@@ -9,7 +9,10 @@ use crate::options::*;
 use crate::screen::Screen;
 use crate::state::AppState;
 
-use crate::state::game::map::LEVEL_COUNT; //TODO: map should be moved to top level
+use crate::state::game::ghosts::reset_stolen_positions;
+use crate::state::game::levels::Levels; //TODO: map should be moved to top level
+use crate::state::game::map::{RivalLevelPositions, RivalPositions};
+use crate::state::game::stats::BestStats;
 
 pub struct Menu;
 
@@ -33,6 +36,7 @@ impl Plugin for Menu {
             .add_system_set(SystemSet::on_enter(AppState::LevelSelect).with_system(ui_camera))
             .add_system_set(SystemSet::on_enter(AppState::LevelSelect).with_system(show_level_select_buttons))
             .add_system_set(SystemSet::on_update(AppState::LevelSelect).with_system(buttons))
+            .add_system_set(SystemSet::on_update(AppState::LevelSelect).with_system(show_level_stats))
             .add_system_set(SystemSet::on_update(AppState::LevelSelect).with_system(move_camera))
             .add_system_set(SystemSet::on_update(AppState::LevelSelect).with_system(update_background))
             .add_system_set(SystemSet::on_exit(AppState::LevelSelect).with_system(clear_background))
@@ -152,10 +156,15 @@ fn show_menu_buttons(
         text: "Options",
         action: Action::ChangeState(AppState::Options),
     }.build(&mut commands, &asset_server, &state);
+    ButtonBuilder {
+        text: "Editor",
+        action: Action::ChangeState(AppState::Editor),
+    }.build(&mut commands, &asset_server, &state);
 }
 
 fn show_level_select_buttons(
     state: Res<State<AppState>>,
+    levels: Res<Levels>,
     mut commands: Commands,
     asset_server: Res<AssetServer>
 ) {
@@ -163,14 +172,40 @@ fn show_level_select_buttons(
         text: "Back",
         action: Action::ChangeState(AppState::Menu),
     }.build(&mut commands, &asset_server, &state);
-    for i in 1..LEVEL_COUNT { //it looks like level 0 is a secret level lol
+    for level in levels.visible() {
         ButtonBuilder {
-            text: format!("Level {}", i),
-            action: Action::Play{ level: i},
+            text: level.name.clone(),
+            action: Action::Play{ level: level.id.clone() },
         }.build(&mut commands, &asset_server, &state);
     }
 }
 
+fn show_level_stats(
+    windows: Res<Windows>,
+    mut egui_context: ResMut<EguiContext>,
+    options: Res<Options>,
+    levels: Res<Levels>,
+    best_stats: Res<BestStats>,
+) {
+    use egui::*;
+
+    let game_window = windows.get_primary().unwrap();
+
+    Window::new("Best runs")
+        .collapsible(false)
+        .resizable(false)
+        .fixed_pos((game_window.width() * 0.7, game_window.height() * 0.1))
+        .show(egui_context.ctx_mut(), |ui| {
+            ui.label(format!("Difficulty: {}", options.difficulty));
+            for level in levels.visible() {
+                match best_stats.get(&level.id, options.difficulty) {
+                    Some(stats) => ui.label(format!("{}: {}", level.name, stats)),
+                    None => ui.label(format!("{}: not won yet", level.name)),
+                };
+            }
+        });
+}
+
 #[derive(Component)]
 struct DifficultyText;
 
@@ -179,6 +214,8 @@ fn show_options_menu(
     mut egui_context: ResMut<EguiContext>,
     mut options: ResMut<Options>,
     mut state: ResMut<State<AppState>>,
+    mut rival_positions: ResMut<RivalPositions>,
+    levels: Res<Levels>,
 ) {
     use egui::*;
     use enum_iterator::IntoEnumIterator;
@@ -202,6 +239,17 @@ fn show_options_menu(
                 });
             ui.label("Volume: ");
             ui.add(Slider::new(&mut options.music_volume, 0..=100));
+            let stolen = Vec::from_iter(levels.iter().filter(|level| {
+                matches!(rival_positions.0.get(&level.id), Some(RivalLevelPositions::Stolen(_)))
+            }));
+            if !stolen.is_empty() {
+                ui.label("Stolen rivals: ");
+                for level in stolen {
+                    if ui.button(format!("Reset {}", level.name)).clicked() {
+                        reset_stolen_positions(&mut rival_positions, &level.id);
+                    }
+                }
+            }
             if ui.button("Back").clicked() {
                 state.set(AppState::Menu).unwrap();
             }
@@ -213,6 +261,7 @@ fn show_leaderboards_ui(
     mut egui_context: ResMut<EguiContext>,
     mut options: ResMut<LeaderBoardOptions>,
     mut state: ResMut<State<AppState>>,
+    levels: Res<Levels>,
     task_pool: Res<AsyncComputeTaskPool>,
 ) {
     use crate::score::*;
@@ -227,12 +276,15 @@ fn show_leaderboards_ui(
         .fixed_pos((game_window.width() * 0.2, game_window.height() * 0.1))
         .show(egui_context.ctx_mut(), |ui| {
             ui.label("Level: ");
+            let selected_level = levels.get(&options.level)
+                .map(|level| level.name.clone())
+                .unwrap_or_else(|| options.level.clone());
             ComboBox::from_id_source("Level select")
-                .selected_text(options.level.to_string())
+                .selected_text(selected_level)
                 .show_ui(ui, |ui| {
                     ui.label("Level: ");
-                    for i in 0..LEVEL_COUNT {
-                        ui.selectable_value(&mut options.level, i, i.to_string());
+                    for level in levels.visible() {
+                        ui.selectable_value(&mut options.level, level.id.clone(), level.name.as_str());
                     }
                 });
             ui.label("Difficulty: ");
@@ -243,7 +295,7 @@ fn show_leaderboards_ui(
                         ui.selectable_value(&mut options.difficulty, difficulty, difficulty.to_string());
                     }
                 });
-            for score in get_scores(options.level.to_string(), options.difficulty, &task_pool) {
+            for score in get_scores(options.level.clone(), options.difficulty, &task_pool) {
                 ui.label(format!("{}: {}", score.username, score.high_score));
             }
             if ui.button("Back").clicked() {
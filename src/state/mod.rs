@@ -11,6 +11,7 @@ pub enum AppState {
     Game,
     GameOver,
     Pause,
+    Editor,
 }
 
 #[derive(Clone)]
@@ -28,7 +29,7 @@ impl Default for GameOverEvent {
     }
 }
 
-mod game;
+pub mod game;
 pub use game::Game;
 mod loading;
 pub use loading::Loading;
@@ -38,3 +39,5 @@ mod game_over;
 pub use game_over::GameOver;
 mod pause;
 pub use pause::Pause;
+mod editor;
+pub use editor::Editor;
@@ -0,0 +1,59 @@
+//! Text that should survive restarting the game, by key.
+//! Natively it is kept in files under the user's data directory, in the browser in localStorage.
+
+pub use platform::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod platform {
+    use std::path::PathBuf;
+
+    fn path(key: &str) -> Option<PathBuf> {
+        Some(dirs::data_dir()?.join("chaz").join(key))
+    }
+
+    pub fn load(key: &str) -> Option<String> {
+        std::fs::read_to_string(path(key)?).ok()
+    }
+
+    pub fn save(key: &str, value: &str) -> Result<(), String> {
+        let path = path(key).ok_or("there is no data directory")?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+        }
+        std::fs::write(path, value).map_err(|e| e.to_string())
+    }
+
+    pub fn remove(key: &str) {
+        if let Some(path) = path(key) {
+            std::fs::remove_file(path).unwrap_or(()); // there was nothing saved
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod platform {
+    fn storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    fn storage_key(key: &str) -> String {
+        format!("chaz/{}", key)
+    }
+
+    pub fn load(key: &str) -> Option<String> {
+        storage()?.get_item(&storage_key(key)).ok()?
+    }
+
+    pub fn save(key: &str, value: &str) -> Result<(), String> {
+        storage()
+            .ok_or("localStorage is not available")?
+            .set_item(&storage_key(key), value)
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    pub fn remove(key: &str) {
+        if let Some(storage) = storage() {
+            storage.remove_item(&storage_key(key)).unwrap_or(());
+        }
+    }
+}
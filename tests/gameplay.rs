@@ -0,0 +1,508 @@
+use std::path::Path;
+
+use bevy::prelude::Vec3;
+
+use chaz::options::{Difficulty, Options};
+use chaz::state::game::harness::{Harness, Health, InputFrame, Lives, Outcome};
+use chaz::state::game::levels::{read_level_manifest, MANIFEST_PATH};
+use chaz::state::game::lint::lint;
+use chaz::state::game::map::{read_map, Map, Tile};
+use chaz::state::game::physics::{read_physics_config, Physics, PHYSICS_PATH};
+
+const IDLE: InputFrame = InputFrame::NONE;
+const RIGHT: InputFrame = InputFrame { right: true, ..InputFrame::NONE };
+const LEFT: InputFrame = InputFrame { left: true, ..InputFrame::NONE };
+const JUMP: InputFrame = InputFrame { jump: true, jump_held: true, ..InputFrame::NONE };
+const HOLD_JUMP: InputFrame = InputFrame { jump_held: true, ..InputFrame::NONE };
+
+fn map(text: &str) -> Map {
+    read_map("harness", text, None).unwrap_or_else(|errors| panic!("{}", errors))
+}
+
+fn harness(text: &str) -> Harness {
+    harness_with(text, Options::default(), Physics::default())
+}
+
+fn harness_on(text: &str, difficulty: Difficulty) -> Harness {
+    harness_with(text, Options { difficulty, ..Options::default() }, Physics::default())
+}
+
+fn harness_with(text: &str, options: Options, physics: Physics) -> Harness {
+    Harness::with_physics(map(text), options, physics)
+}
+
+const JEREMY_MAP: &str = "\
+size: 8x3
+spawn_offset: 0,0
+---
+Ground Empty Empty
+Ground Player Empty
+Ground Empty Empty
+Ground Empty Empty
+Ground Jeremy Empty
+Ground Empty Empty
+Ground Empty Empty
+Ground Win Empty
+";
+
+#[test]
+fn walking_to_the_win_tile_wins() {
+    let mut harness = harness("\
+size: 8x3
+spawn_offset: 0,0
+---
+Ground Empty Empty
+Ground Player Empty
+Ground Empty Empty
+Ground Empty Empty
+Ground Empty Empty
+Ground Win Empty
+Ground Empty Empty
+Ground Empty Empty
+");
+    assert!(matches!(harness.play(&[(300, RIGHT)]), Some(Outcome::Won(_))));
+}
+
+#[test]
+fn coins_and_gems_on_the_way_are_collected() {
+    let mut harness = harness("\
+size: 6x3
+spawn_offset: 0,0
+---
+Ground Empty Empty
+Ground Player Empty
+Ground Coin Empty
+Ground Gem Empty
+Ground Empty Coin
+Ground Win Empty
+");
+    assert!(matches!(harness.play(&[(300, RIGHT)]), Some(Outcome::Won(_))));
+    let stats = harness.stats();
+    assert_eq!((stats.collected, stats.total, stats.stomped), (2, 3, 0));
+    assert!(stats.time > 0.0);
+}
+
+#[test]
+fn a_rival_leaves_the_coins_and_gems_to_the_player() {
+    let text = "\
+size: 6x3
+spawn_offset: 0,0
+---
+Ground Rival Empty
+Ground Player Empty
+Ground Coin Empty
+Ground Gem Empty
+Ground Empty Coin
+Ground Win Empty
+";
+    let options = Options { difficulty: Difficulty::Easy, ..Options::default() };
+    let mut player = Harness::new(map(text), options.clone());
+    let run = match player.play(&[(300, RIGHT)]) {
+        Some(Outcome::Won(run)) => run.clone(),
+        outcome => panic!("Walking to the win tile ended with {:?}", outcome),
+    };
+    let mut raced = map(text);
+    raced.rival = run;
+    let mut race = Harness::new(raced, options);
+    assert!(matches!(race.play(&[(300, IDLE)]), Some(Outcome::RivalWasFaster)));
+    assert_eq!(race.stats().collected, 0);
+    // the level is over, but the ticks still run, and what the rival went through is still there
+    for _ in 0..300 {
+        race.tick(RIGHT);
+    }
+    assert_eq!((race.stats().collected, race.stats().total), (2, 3));
+}
+
+#[test]
+fn standing_still_decides_nothing() {
+    let mut harness = harness("\
+size: 3x3
+spawn_offset: 0,0
+---
+Ground Empty Empty
+Ground Player Empty
+Ground Empty Win
+");
+    let start = harness.player_position();
+    assert!(harness.play(&[(120, IDLE)]).is_none());
+    assert!((harness.player_position() - start).length() < 1.0);
+}
+
+#[test]
+fn a_rival_tile_without_a_recorded_run_does_not_race() {
+    let mut harness = harness("\
+size: 3x3
+spawn_offset: 0,0
+---
+Ground Rival Empty
+Ground Player Empty
+Ground Empty Win
+");
+    assert!(harness.play(&[(60, IDLE)]).is_none());
+}
+
+#[test]
+fn releasing_jump_early_jumps_lower() {
+    let peak = |held: usize| {
+        let mut harness = harness("\
+size: 3x3
+spawn_offset: 0,0
+---
+Ground Empty Empty
+Ground Player Empty
+Ground Empty Win
+");
+        harness.play(&[(10, IDLE), (1, JUMP), (held, HOLD_JUMP)]);
+        let mut peak = harness.player_position().y;
+        for _ in 0..60 {
+            harness.tick(IDLE);
+            peak = peak.max(harness.player_position().y);
+        }
+        peak
+    };
+    assert!(peak(2) + Tile::SIZE < peak(60));
+}
+
+#[test]
+fn the_jump_from_the_ground_is_lost_once_the_coyote_time_is_over() {
+    // with a single jump, so it can only be the one from the ground
+    let physics = Physics { max_jump_count: 1, ..Physics::default() };
+    let jumps = |ticks_off_the_ledge: usize| {
+        let text = "\
+size: 8x5
+spawn_offset: 0,0
+---
+Ground Ground Ground Empty Empty
+Ground Ground Ground Player Empty
+Ground Empty Empty Empty Empty
+Ground Empty Empty Empty Empty
+Ground Empty Empty Empty Empty
+Ground Empty Empty Empty Empty
+Ground Empty Empty Empty Empty
+Ground Win Empty Empty Empty
+";
+        let mut harness = harness_with(text, Options::default(), physics);
+        let ledge = harness.player_position().y;
+        for _ in 0..120 {
+            if harness.player_position().y < ledge {
+                break;
+            }
+            harness.tick(RIGHT);
+        }
+        assert!(harness.player_position().y < ledge, "the player should walk off the ledge");
+        harness.play(&[(ticks_off_the_ledge, RIGHT)]);
+        let before = harness.player_position().y;
+        harness.tick(JUMP);
+        harness.player_position().y > before
+    };
+    let coyote_ticks = (physics.coyote_time * 60.0).round() as usize;
+    assert!(jumps(0));
+    assert!(jumps(coyote_ticks - 2));
+    assert!(!jumps(coyote_ticks + 2));
+}
+
+#[test]
+fn a_jump_pressed_just_before_landing_happens_on_landing() {
+    // with a single jump, so jumping in the air can only be buffered
+    let physics = Physics { max_jump_count: 1, ..Physics::default() };
+    let text = "\
+size: 3x6
+spawn_offset: 0,0
+---
+Ground Empty Empty Empty Empty Empty
+Ground Empty Empty Empty Empty Player
+Ground Empty Empty Empty Empty Win
+";
+    let mut harness = harness_with(text, Options::default(), physics);
+    let mut ground = harness.player_position().y;
+    let mut landing = None;
+    for tick in 0..120 {
+        harness.tick(IDLE);
+        if harness.player_position().y == ground {
+            landing = Some(tick);
+            break;
+        }
+        ground = harness.player_position().y;
+    }
+    let landing = landing.expect("the player should land");
+    let buffer_ticks = (physics.jump_buffer_time * 60.0).round() as usize;
+    let jumps_on_landing = |early: usize| {
+        let mut harness = harness_with(text, Options::default(), physics);
+        harness.play(&[(landing - early, IDLE), (1, JUMP), (early + 10, HOLD_JUMP)]);
+        harness.player_position().y > ground + 1.0
+    };
+    assert!(jumps_on_landing(1));
+    assert!(jumps_on_landing(buffer_ticks - 2));
+    assert!(!jumps_on_landing(buffer_ticks + 4));
+}
+
+#[test]
+fn sliding_down_a_wall_is_slower_than_falling() {
+    let drop = |input: InputFrame| {
+        let mut harness = harness("\
+size: 3x8
+spawn_offset: 0,0
+---
+Ground Ground Ground Ground Ground Ground Ground Ground
+Empty Empty Empty Empty Empty Empty Player Empty
+Empty Empty Empty Empty Empty Empty Empty Win
+");
+        let start = harness.player_position().y;
+        harness.play(&[(30, input)]);
+        start - harness.player_position().y
+    };
+    assert!(drop(LEFT) + Tile::SIZE < drop(IDLE));
+}
+
+#[test]
+fn jumping_while_sliding_down_a_wall_kicks_away_from_it() {
+    let mut harness = harness("\
+size: 3x8
+spawn_offset: 0,0
+---
+Ground Ground Ground Ground Ground Ground Ground Ground
+Empty Empty Empty Empty Empty Empty Player Empty
+Empty Empty Empty Empty Empty Empty Empty Win
+");
+    harness.play(&[(20, LEFT)]);
+    let sliding = harness.player_position();
+    // no direction held, the kick alone moves the player off the wall
+    harness.play(&[(1, JUMP), (10, HOLD_JUMP)]);
+    let jumped = harness.player_position();
+    assert!(jumped.x > sliding.x + Tile::SIZE / 2.0, "{} should be right of {}", jumped, sliding);
+    assert!(jumped.y > sliding.y + Tile::SIZE, "{} should be above {}", jumped, sliding);
+}
+
+#[test]
+fn the_same_inputs_make_the_same_run_at_any_frame_rate() {
+    let text = format!("\
+size: 20x3
+spawn_offset: 0,0
+---
+Ground Empty Win
+Ground Player Empty
+{}", "Ground Empty Empty\n".repeat(18));
+    // every sixth of a second is a whole number of frames at each frame rate
+    let trajectory = |frame_rate: usize| {
+        let mut harness = harness(&text);
+        let mut positions = Vec::new();
+        for frame in 0..3 * frame_rate {
+            harness.frame(1.0 / frame_rate as f32, if frame < frame_rate { RIGHT } else { IDLE });
+            if (frame + 1) % (frame_rate / 6) == 0 {
+                positions.push(harness.player_position());
+            }
+        }
+        positions
+    };
+    let expected = trajectory(60);
+    // rounding can make a frame rate run its ticks one frame later
+    let tolerance = Physics::default().max_run_speed / 60.0 + 0.01;
+    for frame_rate in [30, 144] {
+        let positions = trajectory(frame_rate);
+        assert_eq!(positions.len(), expected.len());
+        for (position, expected) in positions.iter().zip(&expected) {
+            assert!(position.distance(*expected) <= tolerance, "{} Hz: {} instead of {}", frame_rate, position, expected);
+        }
+    }
+}
+
+#[test]
+fn falling_several_tiles_in_a_tick_still_lands() {
+    let text = "\
+size: 3x12
+spawn_offset: 0,0
+---
+Ground Empty Empty Empty Empty Empty Empty Empty Empty Empty Empty Empty
+Ground Empty Empty Empty Empty Empty Empty Empty Empty Empty Empty Player
+Ground Empty Empty Empty Empty Empty Empty Empty Empty Empty Empty Win
+";
+    // falls 11 tiles a tick, from 10 tiles above the ground
+    let physics = Physics { gravity: 1_000_000.0, terminal_speed: 12_000.0, ..Physics::default() };
+    let mut harness = harness_with(text, Options::default(), physics);
+    let start = harness.player_position();
+    assert!(harness.play(&[(30, IDLE)]).is_none());
+    assert!((harness.player_position().y - (start.y - 10.0 * Tile::SIZE)).abs() < 1.0);
+}
+
+#[test]
+fn touching_jeremy_from_the_side_with_half_a_heart_ends_the_game() {
+    let mut harness = harness_on(JEREMY_MAP, Difficulty::Zatoichi);
+    assert_eq!(harness.player_health().max, 1);
+    assert!(matches!(harness.play(&[(300, RIGHT)]), Some(Outcome::KilledByEnemy)));
+}
+
+#[test]
+fn an_enemy_hit_costs_half_a_heart_and_knocks_the_player_back() {
+    let mut harness = harness_on(JEREMY_MAP, Difficulty::Medium);
+    let max = harness.player_health().max;
+    for _ in 0..300 {
+        if harness.player_health().current < max {
+            break;
+        }
+        harness.tick(RIGHT);
+    }
+    assert_eq!(harness.player_health().current, max - 1);
+    let hit = harness.player_position();
+    harness.play(&[(10, IDLE)]);
+    assert!(harness.outcome().is_none());
+    assert_eq!(harness.player_health().current, max - 1);
+    assert!(harness.player_position().x < hit.x);
+}
+
+#[test]
+fn an_enemy_hurts_again_once_the_invulnerability_is_over() {
+    let text = "\
+size: 6x3
+spawn_offset: 0,0
+---
+Ground Empty Empty
+Ground Player Empty
+Ground Blue Empty
+Ground Empty Empty
+Ground Empty Empty
+Ground Win Empty
+";
+    // creeping into the enemy without being knocked back, it keeps touching the player for seconds
+    let physics = Physics { max_run_speed: 6.0, knockback_speed: 0.0, knockback_lift: 0.0, ..Physics::default() };
+    let mut harness = harness_with(text, Options { difficulty: Difficulty::Training, ..Options::default() }, physics);
+    let mut health = harness.player_health().current;
+    let mut hits = Vec::new();
+    for tick in 0..150 {
+        harness.tick(RIGHT);
+        if harness.player_health().current < health {
+            health = harness.player_health().current;
+            hits.push(tick);
+        }
+    }
+    let invulnerability_ticks = (Health::INVULNERABILITY_TIME * 60.0).round() as usize;
+    assert!(hits.len() >= 2, "hurt on ticks {:?}", hits);
+    for pair in hits.windows(2) {
+        assert_eq!(pair[1] - pair[0], invulnerability_ticks, "hurt on ticks {:?}", hits);
+    }
+}
+
+#[test]
+fn falling_off_the_map_ends_the_game() {
+    let mut harness = harness("\
+size: 3x3
+spawn_offset: 0,0
+---
+Empty Player Empty
+Empty Empty Empty
+Empty Empty Win
+");
+    assert!(matches!(harness.play(&[(300, IDLE)]), Some(Outcome::Fell)));
+}
+
+#[test]
+fn falling_after_a_checkpoint_costs_a_life_and_respawns_there() {
+    let mut harness = harness_on("\
+size: 6x3
+spawn_offset: 0,0
+---
+Ground Empty Empty
+Ground Player Empty
+Ground Checkpoint Empty
+Empty Empty Empty
+Empty Empty Empty
+Ground Empty Win
+", Difficulty::Medium);
+    let checkpoint = harness.player_position() + Vec3::X * Tile::SIZE;
+    let lives = harness.player_lives().0;
+    for _ in 0..300 {
+        if harness.player_lives().0 < lives {
+            break;
+        }
+        harness.tick(RIGHT);
+    }
+    assert_eq!(harness.player_lives().0, lives - 1);
+    harness.tick(IDLE);
+    assert!(harness.outcome().is_none());
+    assert!(harness.player_position().distance(checkpoint) < 1.0);
+}
+
+#[test]
+fn falling_on_the_last_life_ends_the_game() {
+    let mut harness = harness_on("\
+size: 3x3
+spawn_offset: 0,0
+---
+Empty Player Empty
+Empty Empty Empty
+Empty Empty Win
+", Difficulty::Medium);
+    assert_eq!(harness.player_lives(), Lives(3));
+    assert!(matches!(harness.play(&[(900, IDLE)]), Some(Outcome::Fell)));
+    assert_eq!(harness.player_lives(), Lives(1));
+}
+
+#[test]
+fn a_rival_replaying_a_run_dies_and_respawns_where_the_player_did() {
+    let text = "\
+size: 4x3
+spawn_offset: 0,0
+---
+Empty Empty Empty
+Ground Player Empty
+Ground Rival Empty
+Ground Win Empty
+";
+    let options = Options { difficulty: Difficulty::Easy, ..Options::default() };
+    let mut player = Harness::new(map(text), options.clone());
+    let lives = player.player_lives();
+    for _ in 0..300 {
+        if player.player_lives() != lives {
+            break;
+        }
+        player.tick(LEFT);
+    }
+    let run = match player.play(&[(300, RIGHT)]) {
+        Some(Outcome::Won(run)) => run.clone(),
+        outcome => panic!("Walking back to the win tile ended with {:?}", outcome),
+    };
+    let finish = player.player_position();
+    // the replay only has the keys pressed, the fall and the respawn come from the rival's own collisions
+    let mut raced = map(text);
+    raced.rival = run;
+    let mut race = Harness::new(raced, options);
+    assert!(matches!(race.play(&[(600, IDLE)]), Some(Outcome::RivalWasFaster)));
+    assert_eq!(race.rival_lives(), Some(Lives(lives.0 - 1)));
+    assert!(race.rival_position().unwrap().truncate().distance(finish.truncate()) < 1e-3);
+}
+
+#[test]
+fn every_level_loads_and_runs() {
+    let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+    let manifest = read_level_manifest(&std::fs::read_to_string(assets.join(MANIFEST_PATH)).unwrap()).unwrap();
+    let physics_config = read_physics_config(&std::fs::read_to_string(assets.join(PHYSICS_PATH)).unwrap()).unwrap();
+    assert!(!manifest.levels.is_empty());
+    for level in manifest.levels {
+        let map = level.read_map_from(&assets).unwrap_or_else(|errors| panic!("{}", errors.join("\n")));
+        let errors = lint(&map, &physics_config, &level.id, Difficulty::default());
+        assert!(errors.is_empty(), "{}: {:?}", level.map, errors);
+        let options = Options { level: level.id.clone(), ..Options::default() };
+        let mut harness = Harness::with_physics_config(map, options, physics_config.clone());
+        let lives = harness.player_lives().0;
+        assert!(harness.play(&[(60, IDLE)]).is_none(), "{} is decided without moving", level.map);
+        assert_eq!(harness.player_lives().0, lives, "{} kills the player without moving", level.map);
+        assert!(harness.player_health().current > 0, "{}", level.map);
+    }
+}
+
+#[test]
+fn physics_overrides_apply_by_level_then_difficulty() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets").join(PHYSICS_PATH);
+    let config = read_physics_config(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(config.physics(Some("1"), Difficulty::Medium), Physics::default());
+
+    let config = read_physics_config("#![enable(implicit_some)]
+(
+    base: (gravity: 600.0),
+    levels: { \"1\": (gravity: 400.0, jump_speed: 450.0) },
+    difficulties: { TRAINING: (jump_speed: 550.0) },
+)").unwrap();
+    let physics = config.physics(Some("1"), Difficulty::Training);
+    assert_eq!((physics.gravity, physics.jump_speed), (400.0, 550.0));
+    assert_eq!(config.physics(None, Difficulty::Medium).gravity, 600.0);
+    assert_eq!(config.physics(Some("2"), Difficulty::Medium).terminal_speed, Physics::default().terminal_speed);
+}